@@ -0,0 +1,92 @@
+//! Compares the fragment and compute persistence paths.
+//!
+//! A long signal is rendered with `glowie capture` at a large size, once per
+//! path, and the best wall-clock time of a few runs is reported. Startup is
+//! included, so compare the two results against each other rather than
+//! reading them as absolute frame times. Set `GLOWIE_SOFTWARE=1` to render on
+//! a software adapter.
+
+use std::f32::consts::PI;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: u32 = 48000;
+const SIZE: u32 = 1440;
+const DURATION: f32 = 5.0;
+const FRAME_RATE: f32 = 60.0;
+const RUNS: usize = 3;
+
+fn write_wav(path: &Path) {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    let num_frames = (DURATION * SAMPLE_RATE as f32) as usize;
+    for i in 0..num_frames {
+        // A Lissajous figure covers most of the screen.
+        let t = i as f32 / SAMPLE_RATE as f32;
+        writer
+            .write_sample(0.9 * (2.0 * PI * 300.0 * t).sin())
+            .unwrap();
+        writer
+            .write_sample(0.9 * (2.0 * PI * 401.0 * t).cos())
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+fn time_capture(wav_path: &Path, out_path: &Path, args: &[&str]) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let mut command = Command::new(env!("CARGO_BIN_EXE_glowie"));
+            command.arg("capture");
+            if std::env::var_os("GLOWIE_SOFTWARE").is_some() {
+                command.arg("--software");
+            }
+            let start = Instant::now();
+            let status = command
+                .arg(wav_path)
+                .arg("--at")
+                .arg(DURATION.to_string())
+                .arg("--out")
+                .arg(out_path)
+                .arg("--width")
+                .arg(SIZE.to_string())
+                .arg("--height")
+                .arg(SIZE.to_string())
+                .arg("--frame-rate")
+                .arg(FRAME_RATE.to_string())
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "capture failed");
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let temp_dir = std::env::temp_dir().join(format!("glowie-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let wav_path = temp_dir.join("lissajous.wav");
+    let out_path = temp_dir.join("lissajous.png");
+    write_wav(&wav_path);
+
+    let frames = (DURATION * FRAME_RATE) as u32;
+    for (name, args) in [
+        ("fragment", &[][..]),
+        ("compute", &["--compute-persistence"][..]),
+    ] {
+        let time = time_capture(&wav_path, &out_path, args);
+        println!(
+            "{name:>8}: {:7.1} ms for {frames} frames at {SIZE}x{SIZE}, {:.2} ms per frame",
+            time.as_secs_f64() * 1e3,
+            time.as_secs_f64() * 1e3 / frames as f64,
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use log::error;
+use pollster::block_on;
+use std::sync::Arc;
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+use winit::window::WindowBuilder;
+
+use crate::capture::{draw_all, FrameReader};
+use crate::gfx::GraphicsContextInner;
+use crate::headless::{self, Offscreen};
+use crate::scope::Scope;
+use crate::source::{FileSource, SampleSource};
+use crate::ArtArgs;
+
+/// Frames read from the file at a time, so that it's never all in memory.
+const CHUNK_FRAMES: usize = 1 << 18;
+
+/// Draw an entire file as a single long exposure, without decay, and either
+/// save it or hold it on screen.
+pub fn art(args: ArtArgs) -> anyhow::Result<()> {
+    let mut feed = Feed::open(&args)?;
+    let size = [args.width, args.height];
+
+    let Some(out) = &args.out else {
+        return show(&args, feed);
+    };
+
+    let instance = GraphicsContextInner::create_instance();
+    let gfx = Arc::new(block_on(GraphicsContextInner::new(
+        &instance,
+        None,
+        args.software,
+    ))?);
+    let target = Offscreen::new(Arc::clone(&gfx), size);
+    let mut scope = Scope::new(Arc::clone(&gfx), headless::FORMAT, size);
+    configure(&mut scope, &args);
+    while feed.next(&mut scope) {
+        draw_all(&gfx, &mut scope, target.view());
+    }
+    target.save_png(out)
+}
+
+fn configure(scope: &mut Scope, args: &ArtArgs) {
+    scope.set_paced(false);
+    scope.set_frozen(true);
+    scope.set_beam(args.beam_profile, args.beam_width);
+}
+
+/// The file, passed to the scope a chunk at a time.
+struct Feed {
+    source: FileSource,
+    reader: FrameReader,
+    frames: Vec<[f32; 2]>,
+    finished: bool,
+}
+
+impl Feed {
+    fn open(args: &ArtArgs) -> anyhow::Result<Self> {
+        let source = FileSource::open(&args.path)?;
+        Ok(Self {
+            reader: FrameReader::new(source.sample_rate(), args.visual_rate),
+            source,
+            frames: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Pass the next chunk of the file to the scope, returning false once the
+    /// whole file has been read.
+    fn next(&mut self, scope: &mut Scope) -> bool {
+        if self.finished {
+            return false;
+        }
+        if self
+            .reader
+            .read(&mut self.source, CHUNK_FRAMES, &mut self.frames)
+            == 0
+        {
+            self.finished = true;
+            return false;
+        }
+        // Nothing may be dropped before it's drawn.
+        scope.set_max_retained(scope.pending() + self.frames.len() + 1);
+        scope.extend(self.frames.drain(..));
+        true
+    }
+}
+
+fn show(args: &ArtArgs, mut feed: Feed) -> anyhow::Result<()> {
+    let event_loop = EventLoop::new()?;
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_title("Glowie")
+            .with_inner_size(PhysicalSize::new(args.width, args.height))
+            .with_resizable(false)
+            .build(&event_loop)?,
+    );
+
+    let instance = GraphicsContextInner::create_instance();
+    let surface = GraphicsContextInner::create_surface(&instance, &window)?;
+    let gfx = Arc::new(block_on(GraphicsContextInner::new(
+        &instance,
+        Some(&surface),
+        args.software,
+    ))?);
+    let surface_caps = surface.get_capabilities(&gfx.adapter);
+    let surface_format = surface_caps
+        .formats
+        .iter()
+        .copied()
+        .find(|f| f.is_srgb())
+        .unwrap_or(surface_caps.formats[0]);
+    let size = window.inner_size();
+    surface.configure(
+        &gfx.device,
+        &wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 1,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+        },
+    );
+
+    let mut scope = Scope::new(Arc::clone(&gfx), surface_format, size.into());
+    configure(&mut scope, args);
+
+    // Redraw continuously until the image is complete; after that, the
+    // frozen trail is just shown again when needed.
+    event_loop.set_control_flow(ControlFlow::Wait);
+    window.request_redraw();
+    event_loop.run(move |event, elwt| {
+        let Event::WindowEvent { event, .. } = event else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => elwt.exit(),
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.state == ElementState::Pressed
+                    && event.logical_key == Key::Named(NamedKey::Escape)
+                {
+                    elwt.exit();
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    Err(err) => {
+                        error!("failed to get frame: {err}");
+                        return;
+                    }
+                };
+                if scope.pending() < CHUNK_FRAMES {
+                    feed.next(&mut scope);
+                }
+                let frame_view = frame.texture.create_view(&Default::default());
+                let mut encoder = gfx.device.create_command_encoder(&Default::default());
+                scope.draw(&frame_view, &mut encoder, &gfx.queue);
+                gfx.queue.submit([encoder.finish()]);
+                frame.present();
+                if scope.pending() > 0 || !feed.finished {
+                    window.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    })?;
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use anyhow::{bail, ensure, Context};
+use cpal::traits::DeviceTrait;
+use cpal::{
+    ChannelCount, FromSample, SampleFormat, SampleRate, SizedSample, Stream, SupportedBufferSize,
+    SupportedStreamConfig,
+};
+use log::{error, info, warn};
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+/// Interval between deliveries of the software clock.
+const CLOCK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Choose the best output configuration for the given channel count and rate.
+///
+/// If the device doesn't support the rate, the closest supported rate is
+/// chosen, and the caller is expected to resample.
+pub fn select_output_config(
+    device: &cpal::Device,
+    channels: ChannelCount,
+    rate: SampleRate,
+) -> anyhow::Result<SupportedStreamConfig> {
+    let device_name = device.name().unwrap_or_else(|_| "unknown device".into());
+    let all_configs: Vec<_> = match device.supported_output_configs() {
+        Ok(configs) => configs.collect(),
+        Err(e) => {
+            warn!("{device_name}: failed to list output configurations: {e}");
+            Vec::new()
+        }
+    };
+    if all_configs.is_empty() {
+        // Some virtual and loopback devices don't report any configurations,
+        // but still have a usable default.
+        let config = device
+            .default_output_config()
+            .with_context(|| format!("{device_name}: device reports no output configurations"))?;
+        ensure!(
+            config.channels() > 0,
+            "{device_name}: device reports no output configurations, and its default has no channels",
+        );
+        if config.channels() != channels {
+            // `build_output_stream` maps the stereo output onto them.
+            info!(
+                "{device_name}: using the default configuration, with {} channels",
+                config.channels()
+            );
+        }
+        return Ok(config);
+    }
+
+    let supported_configs: Vec<_> = all_configs
+        .into_iter()
+        .filter(|cfg| cfg.channels() == channels)
+        .filter(|cfg| is_supported_format(cfg.sample_format()))
+        .collect();
+    // Priorities:
+    // - Floating-point input
+    // - Maximum precision
+    // - Maximum buffer size
+    let priority = |config: &SupportedStreamConfig| {
+        (
+            config.sample_format().is_float(),
+            config.sample_format().sample_size(),
+            match *config.buffer_size() {
+                SupportedBufferSize::Range { max, .. } => max,
+                _ => 0,
+            },
+        )
+    };
+    let exact_config = supported_configs
+        .iter()
+        .cloned()
+        .filter_map(|cfg| cfg.try_with_sample_rate(rate))
+        .max_by_key(priority);
+    match exact_config {
+        Some(config) => Ok(config),
+        None => {
+            // Fall back to the closest supported rate.
+            supported_configs
+                .into_iter()
+                .map(|cfg| {
+                    let closest = rate
+                        .0
+                        .clamp(cfg.min_sample_rate().0, cfg.max_sample_rate().0);
+                    cfg.with_sample_rate(SampleRate(closest))
+                })
+                .min_by_key(|config| {
+                    (
+                        config.sample_rate().0.abs_diff(rate.0),
+                        Reverse(priority(config)),
+                    )
+                })
+                .with_context(|| {
+                    format!("{device_name}: no output configuration has {channels} channels and a supported sample format")
+                })
+        }
+    }
+}
+
+fn is_supported_format(format: SampleFormat) -> bool {
+    matches!(
+        format,
+        SampleFormat::F32 | SampleFormat::I16 | SampleFormat::I32 | SampleFormat::U16
+    )
+}
+
+/// Open an output stream that is filled by `render`, which always works in
+/// interleaved stereo `f32` and is converted to the device's sample format
+/// and channels. A mono device gets the average of both channels, and any
+/// channels past the first two are silent.
+pub fn build_output_stream(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    render: impl FnMut(&mut [f32]) + Send + 'static,
+) -> anyhow::Result<Stream> {
+    match config.sample_format() {
+        SampleFormat::F32 => build_typed_stream::<f32>(device, config, render),
+        SampleFormat::I16 => build_typed_stream::<i16>(device, config, render),
+        SampleFormat::I32 => build_typed_stream::<i32>(device, config, render),
+        SampleFormat::U16 => build_typed_stream::<u16>(device, config, render),
+        format => bail!("unsupported output sample format {format}"),
+    }
+}
+
+fn build_typed_stream<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    mut render: impl FnMut(&mut [f32]) + Send + 'static,
+) -> anyhow::Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels());
+    let mut buffer = Vec::new();
+    let stream = device.build_output_stream::<T, _, _>(
+        &config.config(),
+        move |output_data: &mut [T], _output_info| {
+            buffer.clear();
+            buffer.resize(2 * (output_data.len() / channels), 0.0);
+            render(&mut buffer);
+            for (out, stereo) in output_data.chunks_exact_mut(channels).zip(buffer.chunks(2)) {
+                let (left, right) = (stereo[0], stereo[1]);
+                match out {
+                    [mono] => *mono = T::from_sample(0.5 * (left + right)),
+                    [out_left, out_right, rest @ ..] => {
+                        *out_left = T::from_sample(left);
+                        *out_right = T::from_sample(right);
+                        rest.fill(T::EQUILIBRIUM);
+                    }
+                    [] => {}
+                }
+            }
+        },
+        |stream_error| {
+            error!("stream error: {:?}", stream_error);
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// Call `render` with interleaved stereo buffers at the given rate, paced by
+/// the system clock instead of an audio device.
+pub fn spawn_clock(rate: SampleRate, mut render: impl FnMut(&mut [f32]) + Send + 'static) {
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut delivered = 0u64;
+        let mut buffer = Vec::new();
+        loop {
+            std::thread::sleep(CLOCK_INTERVAL);
+            let due = (start.elapsed().as_secs_f64() * rate.0 as f64) as u64;
+            buffer.clear();
+            buffer.resize(2 * (due - delivered) as usize, 0.0);
+            render(&mut buffer);
+            delivered = due;
+        }
+    });
+}
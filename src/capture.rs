@@ -0,0 +1,118 @@
+use anyhow::ensure;
+use pollster::block_on;
+use std::sync::Arc;
+
+use crate::filter::Resample;
+use crate::gfx::{GraphicsContext, GraphicsContextInner};
+use crate::headless::{self, Offscreen};
+use crate::scope::{Scope, REFERENCE_FRAME_RATE};
+use crate::source::{self, FileSource, SampleSource};
+use crate::CaptureArgs;
+
+/// Render the state of the scope at a point in a file to an image.
+///
+/// All samples up to the given timestamp are drawn, without any real-time
+/// pacing, so the output only depends on the inputs and the GPU.
+pub fn capture(args: CaptureArgs) -> anyhow::Result<()> {
+    let mut source = FileSource::open(&args.path)?;
+    ensure!(args.at >= 0.0, "capture time must not be negative");
+
+    let instance = GraphicsContextInner::create_instance();
+    let gfx = Arc::new(block_on(GraphicsContextInner::new(
+        &instance,
+        None,
+        args.software,
+    ))?);
+    let size = [args.width, args.height];
+    let target = Offscreen::new(Arc::clone(&gfx), size);
+    let mut scope = Scope::new(Arc::clone(&gfx), headless::FORMAT, size);
+    scope.set_paced(false);
+    scope.set_beam(args.beam_profile, args.beam_width);
+    scope.set_normalize_intensity(args.normalize_intensity);
+    scope.set_frame_interval(Some(1.0 / args.frame_rate.unwrap_or(REFERENCE_FRAME_RATE)));
+    scope.set_compute_persistence(args.compute_persistence);
+    if let Some(decay) = args.decay {
+        scope.set_decay(decay);
+    }
+
+    let num_frames = (args.at as f64 * source.sample_rate() as f64) as usize;
+    let frames = read_frames(&mut source, num_frames, args.visual_rate);
+    scope.set_max_retained(frames.len() + 1);
+    match args.frame_rate {
+        Some(frame_rate) => {
+            ensure!(frame_rate > 0.0, "frame rate must be positive");
+            let frame_len = ((args.visual_rate as f32 / frame_rate) as usize).max(1);
+            for frame in frames.chunks(frame_len) {
+                scope.extend(frame.iter().copied());
+                draw_all(&gfx, &mut scope, target.view());
+            }
+        }
+        None => {
+            scope.extend(frames);
+            draw_all(&gfx, &mut scope, target.view());
+        }
+    }
+
+    if let Some(path) = &args.exr {
+        headless::save_exr(&gfx, &scope, path)?;
+    }
+    target.save_png(&args.out)
+}
+
+/// Read up to `limit` frames from the start of a source, resampled to the
+/// visual rate.
+pub fn read_frames(
+    source: &mut impl SampleSource,
+    limit: usize,
+    visual_rate: u32,
+) -> Vec<[f32; 2]> {
+    let mut frames = Vec::new();
+    FrameReader::new(source.sample_rate(), visual_rate).read(source, limit, &mut frames);
+    frames
+}
+
+/// Reads a source in pieces, resampled to the visual rate, keeping the
+/// resampler's state between them.
+pub struct FrameReader {
+    resample: Option<Resample>,
+}
+
+impl FrameReader {
+    pub fn new(sample_rate: u32, visual_rate: u32) -> Self {
+        Self {
+            resample: Resample::new(sample_rate, visual_rate),
+        }
+    }
+
+    /// Append the next `limit` frames of `source` to `out`, returning how many
+    /// were read, which is zero at the end of the source.
+    pub fn read(
+        &mut self,
+        source: &mut impl SampleSource,
+        limit: usize,
+        out: &mut Vec<[f32; 2]>,
+    ) -> usize {
+        let mut count = 0;
+        for frame in source::frames(source).take(limit) {
+            match &mut self.resample {
+                Some(resample) => resample.process(frame, out),
+                None => out.push(frame),
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Draw every pending sample to the target.
+pub fn draw_all(gfx: &GraphicsContext, scope: &mut Scope, target: &wgpu::TextureView) {
+    // Each draw consumes as many samples as fit in the line buffer.
+    loop {
+        let mut encoder = gfx.device.create_command_encoder(&Default::default());
+        scope.draw(target, &mut encoder, &gfx.queue);
+        gfx.queue.submit([encoder.finish()]);
+        if scope.pending() == 0 {
+            break;
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Text commands for controlling playback from a script.
+
+use anyhow::{bail, ensure, Context};
+use clap::ValueEnum;
+use log::warn;
+use std::io::BufRead;
+use winit::event_loop::EventLoopProxy;
+
+use crate::scope::BlendMode;
+use crate::AppEvent;
+
+/// A command, as written on one line of input.
+///
+/// | command             | effect                           |
+/// |---------------------|----------------------------------|
+/// | `pause`             | pause playback                   |
+/// | `play`              | resume playback                  |
+/// | `toggle`            | pause or resume playback         |
+/// | `freeze`            | stop the trail from decaying     |
+/// | `unfreeze`          | let the trail decay again        |
+/// | `set decay <x>`     | fraction retained per sample     |
+/// | `zoom <x>`          | zoom in by a factor              |
+/// | `set zoom <x>`      | zoom to an absolute factor       |
+/// | `color <RRGGBB>`    | set the beam color (sRGB hex)    |
+/// | `set intensity <x>` | beam intensity                   |
+/// | `center <x> <y>`    | move the view to a point         |
+/// | `blend <mode>`      | `replace`, `additive` or `alpha` |
+/// | `seek <seconds>`    | jump to a time in the file       |
+/// | `frame`             | draw one frame (external clock)  |
+/// | `quit`              | exit                             |
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Pause,
+    Play,
+    Toggle,
+    Freeze(bool),
+    SetDecay(f32),
+    Zoom(f32),
+    SetZoom(f32),
+    /// Linear RGB.
+    SetColor([f32; 3]),
+    SetIntensity(f32),
+    SetCenter([f32; 2]),
+    SetBlend(BlendMode),
+    /// Seconds from the start.
+    Seek(f32),
+    Frame,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> anyhow::Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str| -> anyhow::Result<f32> {
+            word.parse()
+                .with_context(|| format!("invalid number {word:?}"))
+        };
+        Ok(match words.as_slice() {
+            ["pause"] => Self::Pause,
+            ["play"] => Self::Play,
+            ["toggle"] => Self::Toggle,
+            ["freeze"] => Self::Freeze(true),
+            ["unfreeze"] => Self::Freeze(false),
+            ["set", "decay", value] => Self::SetDecay(number(value)?),
+            ["zoom", value] => Self::Zoom(number(value)?),
+            ["set", "zoom", value] => Self::SetZoom(number(value)?),
+            ["set", "intensity", value] => Self::SetIntensity(number(value)?),
+            ["center", x, y] => Self::SetCenter([number(x)?, number(y)?]),
+            ["blend", value] => Self::SetBlend(
+                BlendMode::from_str(value, true)
+                    .map_err(|_| anyhow::anyhow!("unknown blend mode {value:?}"))?,
+            ),
+            ["color", value] => {
+                Self::SetColor(crate::parse_color(value).map_err(anyhow::Error::msg)?)
+            }
+            ["seek", value] => {
+                let seconds = number(value)?;
+                ensure!(seconds >= 0.0, "seek time must not be negative");
+                Self::Seek(seconds)
+            }
+            ["frame"] => Self::Frame,
+            ["quit"] => Self::Quit,
+            _ => bail!("unknown command {line:?}"),
+        })
+    }
+}
+
+/// Read commands from stdin on a background thread, and forward them to the
+/// event loop as [`AppEvent::Control`].
+pub fn spawn_stdin(events: EventLoopProxy<AppEvent>) {
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("failed to read control input: {e}");
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Command::parse(&line) {
+                Ok(command) => {
+                    if events.send_event(AppEvent::Control(command)).is_err() {
+                        // The event loop has exited.
+                        break;
+                    }
+                }
+                Err(e) => warn!("{e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        let cases = [
+            ("pause", Command::Pause),
+            ("play", Command::Play),
+            ("toggle", Command::Toggle),
+            ("freeze", Command::Freeze(true)),
+            ("unfreeze", Command::Freeze(false)),
+            ("set decay 0.99", Command::SetDecay(0.99)),
+            ("zoom 2", Command::Zoom(2.0)),
+            ("set zoom 0.5", Command::SetZoom(0.5)),
+            ("set intensity 1e-4", Command::SetIntensity(1e-4)),
+            ("center -0.5 0.25", Command::SetCenter([-0.5, 0.25])),
+            ("blend Additive", Command::SetBlend(BlendMode::Additive)),
+            ("color 000000", Command::SetColor([0.0, 0.0, 0.0])),
+            ("seek 30", Command::Seek(30.0)),
+            ("frame", Command::Frame),
+            ("quit", Command::Quit),
+            // Extra whitespace between words is ignored.
+            ("  set   decay\t0.5 ", Command::SetDecay(0.5)),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(Command::parse(line).unwrap(), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for line in [
+            "set decay high",
+            "zoom 2x",
+            "center 1 y",
+            "seek -1",
+            "seek 1:30",
+            "color 00ff8",
+            "color #gg0000",
+            "blend overlay",
+        ] {
+            assert!(Command::parse(line).is_err(), "{line:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        for line in [
+            "",
+            "stop",
+            "pause now",
+            "set decay",
+            "set speed 2",
+            "center 1",
+        ] {
+            let error = Command::parse(line).unwrap_err();
+            assert!(
+                error.to_string().starts_with("unknown command"),
+                "{line:?}: {error}"
+            );
+        }
+    }
+}
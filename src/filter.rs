@@ -0,0 +1,493 @@
+//! Display-only signal processing, applied to samples on their way to the scope.
+
+use std::f32::consts::PI;
+
+/// The sequence of optional display filters.
+#[derive(Default)]
+pub struct DisplayChain {
+    pub rumble: Option<HighPass>,
+    pub tilt: Option<Tilt>,
+    pub decorrelate: Option<Decorrelate>,
+    /// Converts to the visual sample rate; always last.
+    pub resample: Option<Resample>,
+}
+
+impl DisplayChain {
+    pub fn process(&mut self, frames: &[[f32; 2]], out: &mut Vec<[f32; 2]>) {
+        for &frame in frames {
+            let frame = self.process_frame(frame);
+            match &mut self.resample {
+                Some(resample) => resample.process(frame, out),
+                None => out.push(frame),
+            }
+        }
+    }
+
+    fn process_frame(&mut self, mut frame: [f32; 2]) -> [f32; 2] {
+        if let Some(rumble) = &mut self.rumble {
+            frame = rumble.process(frame);
+        }
+        if let Some(tilt) = &mut self.tilt {
+            frame = tilt.process(frame);
+        }
+        if let Some(decorrelate) = &mut self.decorrelate {
+            frame = decorrelate.process(frame);
+        }
+        frame
+    }
+}
+
+/// Linear-interpolating sample rate converter.
+///
+/// Used to present the scope with points at a fixed rate, independent of the
+/// source, so that trail length and decay look the same for every file.
+pub struct Resample {
+    /// Input samples per output sample.
+    step: f64,
+    /// Position of the next output sample, relative to `prev`.
+    pos: f64,
+    prev: [f32; 2],
+}
+
+impl Resample {
+    /// Returns `None` if no conversion is needed.
+    pub fn new(input_rate: u32, output_rate: u32) -> Option<Self> {
+        (input_rate != output_rate).then(|| Self {
+            step: input_rate as f64 / output_rate as f64,
+            pos: 0.0,
+            prev: [0.0; 2],
+        })
+    }
+
+    pub fn process(&mut self, frame: [f32; 2], out: &mut Vec<[f32; 2]>) {
+        while self.pos < 1.0 {
+            let t = self.pos as f32;
+            out.push([
+                self.prev[0] + t * (frame[0] - self.prev[0]),
+                self.prev[1] + t * (frame[1] - self.prev[1]),
+            ]);
+            self.pos += self.step;
+        }
+        self.pos -= 1.0;
+        self.prev = frame;
+    }
+}
+
+/// Frequency above which the tilt starts to take effect, in Hz.
+const TILT_CORNER: f32 = 200.0;
+
+/// First-order shelving filter that boosts (or cuts) high frequencies, to
+/// make fast, small wiggles visible next to the large excursions of the bass.
+///
+/// The slope is 6 dB/octave between the corner and the end of the shelf,
+/// which is placed so that the average slope up to Nyquist is the requested
+/// one. Low frequencies pass through unchanged.
+pub struct Tilt {
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    prev_in: [f32; 2],
+    prev_out: [f32; 2],
+}
+
+impl Tilt {
+    pub fn new(db_per_octave: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+        let nyquist = 0.5 * sample_rate;
+        let octaves = (nyquist / TILT_CORNER).log2().max(0.0);
+        let gain_db = db_per_octave * octaves;
+        let zero = TILT_CORNER;
+        let pole = (zero * 10f32.powf(gain_db / 20.0)).clamp(1.0, 0.49 * sample_rate);
+
+        // Bilinear transform of (1 + s/zero) / (1 + s/pole), with prewarping.
+        let kz = 1.0 / (PI * zero / sample_rate).tan();
+        let kp = 1.0 / (PI * pole / sample_rate).tan();
+        Self {
+            b0: (1.0 + kz) / (1.0 + kp),
+            b1: (1.0 - kz) / (1.0 + kp),
+            a1: (1.0 - kp) / (1.0 + kp),
+            prev_in: [0.0; 2],
+            prev_out: [0.0; 2],
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let out = std::array::from_fn(|i| {
+            self.b0 * frame[i] + self.b1 * self.prev_in[i] - self.a1 * self.prev_out[i]
+        });
+        self.prev_in = frame;
+        self.prev_out = out;
+        out
+    }
+}
+
+/// Second-order Butterworth high-pass filter, applied to each channel.
+///
+/// Used to remove subsonic content, which moves the beam in large, slow
+/// excursions without being audible.
+pub struct HighPass {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    prev_in: [[f32; 2]; 2],
+    prev_out: [[f32; 2]; 2],
+}
+
+impl HighPass {
+    pub fn new(corner: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+        let corner = corner.clamp(1.0, 0.49 * sample_rate);
+
+        // From the Audio EQ Cookbook, with Q = 1/√2.
+        let w0 = 2.0 * PI * corner / sample_rate;
+        let alpha = w0.sin() * std::f32::consts::FRAC_1_SQRT_2;
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: 0.5 * (1.0 + cos_w0) / a0,
+            b1: -(1.0 + cos_w0) / a0,
+            b2: 0.5 * (1.0 + cos_w0) / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha) / a0,
+            prev_in: [[0.0; 2]; 2],
+            prev_out: [[0.0; 2]; 2],
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let [in1, in2] = self.prev_in;
+        let [out1, out2] = self.prev_out;
+        let out = std::array::from_fn(|i| {
+            self.b0 * frame[i] + self.b1 * in1[i] + self.b2 * in2[i]
+                - self.a1 * out1[i]
+                - self.a2 * out2[i]
+        });
+        self.prev_in = [frame, in1];
+        self.prev_out = [out, out1];
+        out
+    }
+}
+
+const HILBERT_TAPS: usize = 31;
+
+/// Shifts the second channel by 90° relative to the first, using a windowed
+/// FIR Hilbert transformer.
+///
+/// Mono content, which would otherwise collapse to a diagonal line, opens up
+/// into an ellipse. This distorts the stereo image, and should not be used
+/// for measurement.
+pub struct Decorrelate {
+    taps: [f32; HILBERT_TAPS],
+    history: [[f32; 2]; HILBERT_TAPS],
+    pos: usize,
+}
+
+impl Decorrelate {
+    pub fn new() -> Self {
+        let center = (HILBERT_TAPS / 2) as isize;
+        let taps = std::array::from_fn(|n| {
+            let k = n as isize - center;
+            if k % 2 == 0 {
+                0.0
+            } else {
+                // Hamming window
+                let window = 0.54 - 0.46 * (2.0 * PI * n as f32 / (HILBERT_TAPS - 1) as f32).cos();
+                window * 2.0 / (PI * k as f32)
+            }
+        });
+        Self {
+            taps,
+            history: [[0.0; 2]; HILBERT_TAPS],
+            pos: 0,
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        self.history[self.pos] = frame;
+        self.pos = (self.pos + 1) % HILBERT_TAPS;
+
+        // `pos` is now the oldest sample. The first channel is delayed by the
+        // filter's group delay, to stay aligned with the second.
+        let x = self.history[(self.pos + HILBERT_TAPS / 2) % HILBERT_TAPS][0];
+        let y = self
+            .taps
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, tap)| tap * self.history[(self.pos + i) % HILBERT_TAPS][1])
+            .sum();
+        [x, y]
+    }
+}
+
+impl Default for Decorrelate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time constant of the color smoothing, in seconds.
+const COLOR_SMOOTHING: f32 = 0.2;
+
+/// Derives a beam color from the brightness of the signal.
+///
+/// The spectral centroid is estimated from the ratio of the power of the
+/// signal's derivative to the power of the signal itself, which for a pure
+/// tone is proportional to its frequency. Dull, bass-heavy content maps to
+/// red, moving through yellow and green to blue for bright content.
+pub struct ReactiveColor {
+    sample_rate: f32,
+    prev: [f32; 2],
+    /// Smoothed centroid estimate, relative to the Nyquist frequency.
+    centroid: f32,
+}
+
+impl ReactiveColor {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            prev: [0.0; 2],
+            centroid: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, frames: &[[f32; 2]]) {
+        let mut power = 0.0;
+        let mut diff_power = 0.0;
+        for &frame in frames {
+            for (x, prev) in frame.into_iter().zip(self.prev) {
+                power += x * x;
+                diff_power += (x - prev) * (x - prev);
+            }
+            self.prev = frame;
+        }
+        if power < 1e-9 {
+            // Silence has no meaningful centroid; hold the last color.
+            return;
+        }
+        // A sine at normalized frequency f has diff/signal power ratio
+        // 4 sin²(πf/2), which is 4 at Nyquist.
+        let centroid = (diff_power / power / 4.0).sqrt().min(1.0).asin() * 2.0 / PI;
+        let alpha = 1.0 - (-(frames.len() as f32) / (COLOR_SMOOTHING * self.sample_rate)).exp();
+        self.centroid += alpha * (centroid - self.centroid);
+    }
+
+    /// The current beam color, in linear RGB.
+    pub fn color(&self) -> [f32; 3] {
+        // Most musical content sits in the lower part of the spectrum.
+        let hue = 240.0 * self.centroid.sqrt();
+        hue_to_rgb(hue)
+    }
+}
+
+/// Fully saturated color for a hue in degrees.
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let h = (hue / 60.0).rem_euclid(6.0);
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+/// Lowest frequency that [`PeriodDetector`] looks for, in Hz.
+const MIN_ALIGN_FREQUENCY: u32 = 20;
+
+/// Rate at which [`PeriodDetector`] updates its estimate, in Hz.
+const ALIGN_ESTIMATE_RATE: u32 = 10;
+
+/// Rate at which [`PeriodDetector`] makes its first, coarse search, in Hz.
+const ALIGN_ANALYSIS_RATE: u32 = 8000;
+
+/// Estimates the period of a repeating signal, by autocorrelation.
+///
+/// The estimate is the lag of the first correlation peak above the threshold
+/// after the correlation first goes negative, so that the main lobe around
+/// zero lag, where every signal correlates with itself, is skipped. To keep
+/// the cost independent of the sample rate, the peak is found in a decimated
+/// copy of the signal, then refined at the full rate.
+pub struct PeriodDetector {
+    /// Minimum normalized correlation for an estimate to be trusted.
+    threshold: f32,
+    /// Length of the correlated window, and the longest period.
+    window: usize,
+    /// Samples averaged into each one of the coarse search.
+    decimation: usize,
+    estimate_interval: usize,
+    /// The latest samples, mixed to mono.
+    history: Vec<f32>,
+    /// `history`, decimated.
+    coarse: Vec<f32>,
+    since_estimate: usize,
+    period: Option<usize>,
+}
+
+impl PeriodDetector {
+    pub fn new(threshold: f32, sample_rate: u32) -> Self {
+        let window = (sample_rate / MIN_ALIGN_FREQUENCY).max(2) as usize;
+        Self {
+            threshold,
+            window,
+            decimation: (sample_rate / ALIGN_ANALYSIS_RATE).max(1) as usize,
+            estimate_interval: (sample_rate / ALIGN_ESTIMATE_RATE) as usize,
+            history: Vec::with_capacity(4 * window),
+            coarse: Vec::new(),
+            since_estimate: 0,
+            period: None,
+        }
+    }
+
+    pub fn process(&mut self, frames: &[[f32; 2]]) {
+        self.history
+            .extend(frames.iter().map(|&[x, y]| 0.5 * (x + y)));
+        // Correlating a window against lags up to its length needs two windows.
+        let needed = 2 * self.window;
+        if self.history.len() > needed {
+            self.history.drain(..self.history.len() - needed);
+        }
+        self.since_estimate += frames.len();
+        if self.since_estimate >= self.estimate_interval && self.history.len() == needed {
+            self.since_estimate = 0;
+            self.period = self.estimate();
+        }
+    }
+
+    /// The estimated period, in samples, if the signal is periodic enough.
+    pub fn period(&self) -> Option<usize> {
+        self.period
+    }
+
+    fn estimate(&mut self) -> Option<usize> {
+        let decimation = self.decimation;
+        self.coarse.clear();
+        self.coarse.extend(
+            self.history
+                .chunks_exact(decimation)
+                .map(|chunk| chunk.iter().sum::<f32>() / decimation as f32),
+        );
+        let coarse_lag = first_peak(&self.coarse, self.window / decimation, self.threshold)?;
+        if decimation == 1 {
+            return Some(coarse_lag);
+        }
+
+        // The coarse lag is within one decimated sample of the true period.
+        let center = coarse_lag * decimation;
+        let lags =
+            center.saturating_sub(decimation).max(1)..=(center + decimation).min(self.window - 1);
+        let reference_power = power(&self.history[..self.window]);
+        lags.map(|lag| {
+            (
+                lag,
+                correlation(&self.history, self.window, lag, reference_power),
+            )
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(lag, _)| lag)
+    }
+}
+
+fn power(signal: &[f32]) -> f32 {
+    signal.iter().map(|x| x * x).sum()
+}
+
+/// Normalized correlation of the first `window` samples of `signal` with the
+/// ones `lag` samples later.
+fn correlation(signal: &[f32], window: usize, lag: usize, reference_power: f32) -> f32 {
+    let reference = &signal[..window];
+    let shifted = &signal[lag..lag + window];
+    let (dot, power) = reference
+        .iter()
+        .zip(shifted)
+        .fold((0.0, 0.0), |(dot, power), (a, b)| {
+            (dot + a * b, power + b * b)
+        });
+    dot / (reference_power * power).sqrt().max(1e-9)
+}
+
+/// The lag of the first correlation peak above `threshold`, past the main
+/// lobe, searching lags shorter than `window`.
+fn first_peak(signal: &[f32], window: usize, threshold: f32) -> Option<usize> {
+    let reference_power = power(&signal[..window]);
+    if reference_power < 1e-9 {
+        return None;
+    }
+
+    let mut past_main_lobe = false;
+    let mut best_lag = None;
+    let mut best_correlation = threshold;
+    for lag in 1..window {
+        let correlation = correlation(signal, window, lag, reference_power);
+        if !past_main_lobe {
+            past_main_lobe = correlation < 0.0;
+            continue;
+        }
+        if correlation > best_correlation {
+            best_lag = Some(lag);
+            best_correlation = correlation;
+        } else if best_lag.is_some() && correlation < threshold {
+            // Past the first peak; later ones are multiples of the period.
+            break;
+        }
+    }
+    best_lag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `seconds` of a signal to a detector, in audio-callback-sized
+    /// batches, returning its final estimate.
+    fn detect(sample_rate: u32, seconds: f32, signal: impl Fn(f32) -> f32) -> Option<usize> {
+        let mut detector = PeriodDetector::new(0.8, sample_rate);
+        let frames: Vec<[f32; 2]> = (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| {
+                let x = signal(i as f32 / sample_rate as f32);
+                [x, x]
+            })
+            .collect();
+        for batch in frames.chunks(512) {
+            detector.process(batch);
+        }
+        detector.period()
+    }
+
+    #[test]
+    fn detects_sine_period() {
+        for (sample_rate, frequency) in [(48000, 120.0), (48000, 440.0), (192000, 300.0)] {
+            let expected = sample_rate as f32 / frequency;
+            let period = detect(sample_rate, 0.5, |t| (2.0 * PI * frequency * t).sin())
+                .unwrap_or_else(|| {
+                    panic!("no period found for {frequency} Hz at {sample_rate} Hz")
+                });
+            assert!(
+                (period as f32 - expected).abs() <= 1.0,
+                "{frequency} Hz at {sample_rate} Hz: period {period}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn detects_fundamental_of_harmonics() {
+        // The second harmonic makes a peak at half the period, but a weak one.
+        let period = detect(48000, 0.5, |t| {
+            (2.0 * PI * 100.0 * t).sin() + 0.5 * (2.0 * PI * 200.0 * t + 1.0).sin()
+        });
+        assert_eq!(
+            period.map(|p| p.abs_diff(480) <= 1),
+            Some(true),
+            "{period:?}"
+        );
+    }
+
+    #[test]
+    fn silence_has_no_period() {
+        assert_eq!(detect(48000, 0.5, |_| 0.0), None);
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Context};
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use winit::window::Window;
+
+/// Attempts at creating a surface or adapter before giving up, for transient
+/// failures such as a display being hotplugged or a compositor restarting.
+const ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Power preferences to try in turn, in case some adapter fails to open on a
+/// multi-GPU system.
+const POWER_PREFERENCES: [wgpu::PowerPreference; ATTEMPTS as usize] = [
+    wgpu::PowerPreference::None,
+    wgpu::PowerPreference::HighPerformance,
+    wgpu::PowerPreference::LowPower,
+    wgpu::PowerPreference::None,
+];
+
+fn retry_delay(attempt: u32) -> Duration {
+    FIRST_RETRY_DELAY * 2u32.pow(attempt)
+}
+
+pub type GraphicsContext = Arc<GraphicsContextInner>;
+
+pub struct GraphicsContextInner {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl GraphicsContextInner {
+    pub fn create_instance() -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        })
+    }
+
+    /// Create a surface for a window, retrying on failure.
+    pub fn create_surface(
+        instance: &wgpu::Instance,
+        window: &Arc<Window>,
+    ) -> anyhow::Result<wgpu::Surface<'static>> {
+        let mut attempt = 0;
+        loop {
+            match instance.create_surface(Arc::clone(window)) {
+                Ok(surface) => return Ok(surface),
+                Err(e) if attempt + 1 < ATTEMPTS => {
+                    warn!("failed to create surface, retrying: {e}");
+                    std::thread::sleep(retry_delay(attempt));
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e)
+                        .context(format!("surface creation failed after {ATTEMPTS} attempts"))
+                }
+            }
+        }
+    }
+
+    /// Open a device. If a surface is given, the device will be able to present to it.
+    ///
+    /// With `software`, only a fallback adapter is used, such as lavapipe or
+    /// WARP, so that rendering works and is reproducible without a GPU.
+    pub async fn new(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+        software: bool,
+    ) -> anyhow::Result<Self> {
+        let mut adapter = None;
+        for (attempt, power_preference) in (0..).zip(POWER_PREFERENCES) {
+            if attempt > 0 {
+                std::thread::sleep(retry_delay(attempt - 1));
+            }
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference,
+                    compatible_surface,
+                    force_fallback_adapter: software,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter
+            .ok_or_else(|| no_adapter_error(instance, software, compatible_surface.is_some()))?;
+
+        let info = adapter.get_info();
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    // Only core features are used, so that any adapter works.
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to open device on {} ({:?})",
+                    info.name, info.backend
+                )
+            })?;
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
+
+/// Explain why no adapter was found, listing the adapters that do exist.
+fn no_adapter_error(instance: &wgpu::Instance, software: bool, for_surface: bool) -> anyhow::Error {
+    let kind = if software {
+        "software adapter"
+    } else {
+        "adapter"
+    };
+    let adapters: Vec<String> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .map(|adapter| {
+            let info = adapter.get_info();
+            format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+        })
+        .collect();
+    if adapters.is_empty() {
+        anyhow!("no compatible {kind}: no graphics adapters were found")
+    } else if for_surface {
+        anyhow!(
+            "no compatible {kind}: none of these can present to the window: {}",
+            adapters.join(", ")
+        )
+    } else {
+        anyhow!("no compatible {kind} among: {}", adapters.join(", "))
+    }
+}
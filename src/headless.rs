@@ -0,0 +1,149 @@
+use anyhow::Context;
+use std::path::Path;
+
+use crate::gfx::GraphicsContext;
+use crate::scope::Scope;
+
+/// Color format used for offscreen rendering.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// An offscreen render target that can be read back to the CPU.
+pub struct Offscreen {
+    gfx: GraphicsContext,
+    size: [u32; 2],
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl Offscreen {
+    pub fn new(gfx: GraphicsContext, size: [u32; 2]) -> Self {
+        let size = size.map(|x| x.max(1));
+        let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen.texture"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                ..Default::default()
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self {
+            gfx,
+            size,
+            texture,
+            view,
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Copy the current contents to the CPU, as tightly-packed RGBA8 rows.
+    pub fn read_rgba(&self) -> anyhow::Result<Vec<u8>> {
+        read_texture(&self.gfx, &self.texture)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let [width, height] = self.size;
+        image::save_buffer(
+            path,
+            &self.read_rgba()?,
+            width,
+            height,
+            image::ColorType::Rgba8,
+        )
+        .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Copy the contents of a texture to the CPU, as tightly-packed rows.
+pub fn read_texture(gfx: &GraphicsContext, texture: &wgpu::Texture) -> anyhow::Result<Vec<u8>> {
+    let size = texture.size();
+    let texel_bytes = texture
+        .format()
+        .block_size(None)
+        .context("texture format can't be copied")?;
+    let row_bytes = texel_bytes * size.width;
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("headless.readback"),
+        size: (padded_row_bytes * size.height).into(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = gfx.device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+    gfx.queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = tx.send(result);
+    });
+    gfx.device.poll(wgpu::Maintain::Wait);
+    rx.recv()
+        .context("readback was cancelled")?
+        .context("failed to map readback buffer")?;
+
+    let mapped = slice.get_mapped_range();
+    let mut data = Vec::with_capacity((row_bytes * size.height) as usize);
+    for row in mapped.chunks(padded_row_bytes as usize) {
+        data.extend_from_slice(&row[..row_bytes as usize]);
+    }
+    drop(mapped);
+    buffer.unmap();
+
+    Ok(data)
+}
+
+/// Save the scope's persistence buffer to an OpenEXR file, as linear floats,
+/// with the beam energy in the `Y` channel and the afterglow in `afterglow`.
+///
+/// Unlike a screenshot, this keeps the full dynamic range of the beam,
+/// without any color mapping.
+pub fn save_exr(
+    gfx: &GraphicsContext,
+    scope: &Scope,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    use exr::prelude::{Image, SpecificChannels, Vec2, WritableImage};
+
+    let path = path.as_ref();
+    let texture = scope.persistence();
+    let width = texture.width() as usize;
+    let height = texture.height() as usize;
+    let texels: Vec<[f32; 2]> = bytemuck::pod_collect_to_vec(&read_texture(gfx, texture)?);
+
+    let channels = SpecificChannels::build()
+        .with_channel("Y")
+        .with_channel("afterglow")
+        .with_pixel_fn(|position: Vec2<usize>| {
+            let [beam, afterglow] = texels[position.y() * width + position.x()];
+            (beam, afterglow)
+        });
+    Image::from_channels((width, height), channels)
+        .write()
+        .to_file(path)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,135 @@
+//! Parameter control over a local HTTP endpoint.
+//!
+//! `GET /params` returns the current [`Params`] as JSON. `POST /params` takes
+//! a JSON object with any subset of the same fields, and applies them.
+
+use anyhow::anyhow;
+use log::warn;
+use serde::Deserialize;
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tiny_http::{Header, Method, Request, Response, Server};
+use winit::event_loop::EventLoopProxy;
+
+use crate::control::Command;
+use crate::scope::BlendMode;
+use crate::AppEvent;
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Snapshot of the adjustable parameters, kept up to date by the event loop
+/// for controllers that need to read them back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Params {
+    pub paused: bool,
+    pub frozen: bool,
+    pub decay: f32,
+    pub zoom: f32,
+    /// Linear RGB.
+    pub beam_color: [f32; 3],
+    pub intensity: f32,
+    /// Sample coordinate shown at the center of the screen.
+    pub center: [f32; 2],
+    pub blend: BlendMode,
+}
+
+pub type SharedParams = Arc<Mutex<Params>>;
+
+/// Fields of a `POST /params` body. Missing fields are left unchanged.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ParamsUpdate {
+    paused: Option<bool>,
+    frozen: Option<bool>,
+    decay: Option<f32>,
+    zoom: Option<f32>,
+    beam_color: Option<[f32; 3]>,
+    intensity: Option<f32>,
+    center: Option<[f32; 2]>,
+    blend: Option<BlendMode>,
+}
+
+impl ParamsUpdate {
+    fn commands(&self) -> impl Iterator<Item = Command> {
+        [
+            self.paused.map(|paused| {
+                if paused {
+                    Command::Pause
+                } else {
+                    Command::Play
+                }
+            }),
+            self.frozen.map(Command::Freeze),
+            self.decay.map(Command::SetDecay),
+            self.zoom.map(Command::SetZoom),
+            self.beam_color.map(Command::SetColor),
+            self.intensity.map(Command::SetIntensity),
+            self.center.map(Command::SetCenter),
+            self.blend.map(Command::SetBlend),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Serve the API on a background thread, forwarding changes to the event loop
+/// as [`AppEvent::Control`].
+pub fn spawn(
+    addr: SocketAddr,
+    params: SharedParams,
+    events: EventLoopProxy<AppEvent>,
+) -> anyhow::Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("http control on {addr}: {e}"))?;
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let response = handle(&mut request, &params, &events);
+            if let Err(e) = request.respond(response) {
+                warn!("http control: failed to respond: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(
+    request: &mut Request,
+    params: &SharedParams,
+    events: &EventLoopProxy<AppEvent>,
+) -> HttpResponse {
+    let method = request.method().clone();
+    let url = request.url().to_owned();
+    match (method, url.as_str()) {
+        (Method::Get, "/params") => {
+            let params: Params = *params.lock().unwrap();
+            json(&params)
+        }
+        (Method::Post, "/params") => {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                return text(400, &format!("failed to read body: {e}"));
+            }
+            let update: ParamsUpdate = match serde_json::from_str(&body) {
+                Ok(update) => update,
+                Err(e) => return text(400, &format!("invalid params: {e}")),
+            };
+            for command in update.commands() {
+                if events.send_event(AppEvent::Control(command)).is_err() {
+                    return text(503, "shutting down");
+                }
+            }
+            text(204, "")
+        }
+        (_, "/params") => text(405, "method not allowed"),
+        _ => text(404, "not found"),
+    }
+}
+
+fn json(value: &impl serde::Serialize) -> HttpResponse {
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_string(serde_json::to_string(value).unwrap()).with_header(header)
+}
+
+fn text(status: u16, message: &str) -> HttpResponse {
+    Response::from_string(message).with_status_code(status)
+}
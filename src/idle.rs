@@ -0,0 +1,91 @@
+//! Built-in pattern that is drawn while there is no signal.
+
+use std::f64::consts::TAU;
+use std::time::{Duration, Instant};
+
+/// How long the input must be silent before the pattern appears.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Samples quieter than this are considered silence.
+const SILENCE_THRESHOLD: f32 = 1e-4;
+
+/// Frequencies of the two axes, in Hz. Their 3:2 ratio gives a closed figure.
+const FREQUENCIES: [f64; 2] = [90.0, 60.0];
+
+/// Rate at which the figure rotates, in radians per second.
+const ROTATION_RATE: f64 = 0.3;
+
+const AMPLITUDE: f64 = 0.5;
+
+/// Longest gap that is filled in, such as after a pause.
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+/// A slowly rotating Lissajous figure, generated in real time whenever the
+/// input has been silent for a while.
+pub struct IdlePattern {
+    rate: u32,
+    /// Time of the last frame that wasn't silent.
+    last_signal: Instant,
+    /// Time up to which frames have been generated, while idle.
+    generated_until: Option<Instant>,
+    /// Position in the pattern, in frames.
+    position: u64,
+}
+
+impl IdlePattern {
+    /// `rate` is the rate of the generated frames.
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            last_signal: Instant::now(),
+            generated_until: None,
+            position: 0,
+        }
+    }
+
+    /// Watch incoming frames for signal, which stops the pattern.
+    pub fn observe(&mut self, frames: &[[f32; 2]]) {
+        let has_signal = frames
+            .iter()
+            .any(|frame| frame.iter().any(|x| x.abs() > SILENCE_THRESHOLD));
+        if has_signal {
+            self.last_signal = Instant::now();
+            self.generated_until = None;
+        }
+    }
+
+    /// Whether the input has been silent long enough for the pattern to show.
+    pub fn is_active(&self) -> bool {
+        self.last_signal.elapsed() >= IDLE_TIMEOUT
+    }
+
+    /// Generate the pattern frames that are due since the last call, if the
+    /// input is idle.
+    pub fn generate(&mut self, out: &mut Vec<[f32; 2]>) {
+        if !self.is_active() {
+            return;
+        }
+        let now = Instant::now();
+        let Some(since) = self.generated_until.replace(now) else {
+            // Just went idle; start generating from here.
+            return;
+        };
+        let since = since.max(now - MAX_CATCH_UP);
+        let count = (now.duration_since(since).as_secs_f64() * self.rate as f64) as u64;
+        // Don't lose the fractional frame.
+        let count_time = Duration::from_secs_f64(count as f64 / self.rate as f64);
+        self.generated_until = Some(since + count_time);
+
+        out.extend((0..count).map(|_| {
+            let t = self.position as f64 / self.rate as f64;
+            self.position += 1;
+            let x = (TAU * FREQUENCIES[0] * t).sin();
+            let y = (TAU * FREQUENCIES[1] * t).sin();
+            let (sin, cos) = (ROTATION_RATE * t).sin_cos();
+            [
+                (AMPLITUDE * (x * cos - y * sin)) as f32,
+                (AMPLITUDE * (x * sin + y * cos)) as f32,
+            ]
+        }));
+    }
+}
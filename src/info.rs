@@ -0,0 +1,56 @@
+use anyhow::Context;
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::SampleRate;
+
+use crate::audio;
+use crate::InfoArgs;
+
+/// Describe a file, and how it would be played on the default device.
+pub fn info(args: InfoArgs) -> anyhow::Result<()> {
+    let mut reader = audrey::open(&args.path)
+        .with_context(|| format!("failed to open {}", args.path.display()))?;
+    let descr = reader.description();
+    let channels = descr.channel_count();
+    let sample_rate = descr.sample_rate();
+    let samples = reader.samples::<f32>().take_while(Result::is_ok).count();
+    let frames = samples / channels.max(1) as usize;
+    let seconds = frames as f64 / sample_rate.max(1) as f64;
+
+    println!("file:        {}", args.path.display());
+    println!("container:   {:?}", descr.format());
+    println!("channels:    {channels}");
+    println!("sample rate: {sample_rate} Hz");
+    println!(
+        "length:      {frames} frames ({}:{:06.3})",
+        (seconds / 60.0) as u64,
+        seconds % 60.0
+    );
+
+    if channels != 2 {
+        println!("playback:    not supported, only stereo files can be played");
+        return Ok(());
+    }
+    let host = cpal::default_host();
+    let Some(device) = host.default_output_device() else {
+        println!("playback:    no default output device");
+        return Ok(());
+    };
+    let device_name = device.name().unwrap_or_else(|_| "unknown device".into());
+    match audio::select_output_config(&device, 2, SampleRate(sample_rate)) {
+        Ok(config) if config.sample_rate().0 == sample_rate => {
+            println!(
+                "playback:    direct on {device_name} ({})",
+                config.sample_format()
+            );
+        }
+        Ok(config) => {
+            println!(
+                "playback:    resampled to {} Hz on {device_name} ({})",
+                config.sample_rate().0,
+                config.sample_format()
+            );
+        }
+        Err(e) => println!("playback:    not possible: {e:#}"),
+    }
+    Ok(())
+}
@@ -1,137 +1,707 @@
+mod art;
+mod audio;
+mod capture;
+mod control;
+mod filter;
+mod gfx;
+mod headless;
+#[cfg(feature = "http-control")]
+mod http;
+mod idle;
+mod info;
+#[cfg(feature = "media-keys")]
+mod media;
+mod queue;
+mod resample;
+mod resume;
 mod scope;
+mod source;
 
 use anyhow::{ensure, Context};
 use clap::Parser;
+use control::Command as ControlCommand;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{ChannelCount, SampleRate, SupportedBufferSize};
+use cpal::{ChannelCount, SampleRate};
+use filter::{Decorrelate, DisplayChain, HighPass, PeriodDetector, ReactiveColor, Resample, Tilt};
+use gfx::{GraphicsContext, GraphicsContextInner};
+use glam::Vec2;
+#[cfg(feature = "http-control")]
+use http::{Params, SharedParams};
+use idle::IdlePattern;
+use log::{debug, error, info, warn, LevelFilter};
+#[cfg(feature = "media-keys")]
+use media::Media;
 use pollster::block_on;
-use scope::Scope;
-use std::iter::repeat;
+use queue::SampleQueue;
+use resample::{Interpolation, Resampler};
+use resume::Resume;
+use scope::{Afterglow, BeamProfile, BlendMode, Mirror, Phosphor, Polar, Scope};
+use source::{FileSource, SampleSource};
+#[cfg(feature = "media-keys")]
+use souvlaki::MediaControlEvent;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use thingbuf::recycling::WithCapacity;
-use thingbuf::ThingBuf;
-use winit::dpi::LogicalSize;
-use winit::event::{Event, WindowEvent};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoopBuilder};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
 use winit::window::{Window, WindowBuilder};
 
 #[derive(Debug, Clone, clap::Parser)]
 struct Args {
     #[clap(subcommand)]
     command: Command,
+
+    /// Only report fatal errors.
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Report more about what glowie is doing. Repeat for more detail.
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+}
+
+impl Args {
+    /// Level of glowie's own messages. Other crates only report errors,
+    /// unless `RUST_LOG` says otherwise.
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Off;
+        }
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
 }
 
 #[derive(Debug, Clone, clap::Subcommand)]
 enum Command {
     Play(PlayArgs),
+    /// Render a single frame of a file to an image.
+    Capture(CaptureArgs),
+    /// Draw a whole file as a single long exposure.
+    Art(ArtArgs),
+    /// Describe a file, and whether it can be played on the default device.
+    Info(InfoArgs),
 }
 
 #[derive(Debug, Clone, clap::Parser)]
 struct PlayArgs {
     path: PathBuf,
+
+    /// Which point in the signal chain is shown on the scope.
+    ///
+    /// `processed` shows exactly what is sent to the audio device; `raw` shows
+    /// the signal as read from the source.
+    #[clap(long, value_enum, default_value_t = ScopeTap::Processed)]
+    scope_tap: ScopeTap,
+
+    /// Intensity falloff across the beam.
+    #[clap(long, value_enum, default_value_t = BeamProfile::Gaussian)]
+    beam_profile: BeamProfile,
+
+    /// Width of the beam profile, where the screen is 2.0 wide.
+    #[clap(long, default_value_t = 5e-3)]
+    beam_width: f32,
+
+    /// Phase-shift one channel by 90° on the scope, so that mono content is
+    /// displayed as a shape instead of a line.
+    ///
+    /// This is a display aid only; it does not affect the audio, and the
+    /// resulting image is not a faithful measurement of the stereo field.
+    #[clap(long)]
+    decorrelate: bool,
+
+    /// Boost high frequencies on the scope by this many dB per octave, so
+    /// that fine detail isn't swamped by the bass. Negative values cut them
+    /// instead. Doesn't affect the audio.
+    #[clap(long, allow_negative_numbers = true)]
+    tilt: Option<f32>,
+
+    /// Remove content below this frequency, in Hz, from the scope, so that
+    /// subsonic rumble doesn't make the image wander. Without a value, the
+    /// corner is 20 Hz. Doesn't affect the audio.
+    #[clap(long, value_name = "HZ", num_args = 0..=1, default_missing_value = "20")]
+    rumble_filter: Option<f32>,
+
+    /// Detect the period of a repeating signal, and draw whole periods each
+    /// frame, so that every frame starts at the same phase. The value is the
+    /// minimum correlation, from 0 to 1, for the signal to count as periodic;
+    /// below it, drawing runs free. Without a value, it is 0.8.
+    #[clap(long, value_name = "THRESHOLD", num_args = 0..=1, default_missing_value = "0.8")]
+    auto_align: Option<f32>,
+
+    /// Don't play any sound; only visualize the file, in real time.
+    #[clap(long)]
+    no_audio: bool,
+
+    /// Read commands from stdin, one per line, such as `pause` or
+    /// `set decay 0.999`.
+    #[clap(long)]
+    control_stdin: bool,
+
+    /// Draw a frame only when a `frame` command is received, instead of as
+    /// fast as the display allows, so that several instances can be kept in
+    /// step by an external clock. Requires `--control-stdin`.
+    #[clap(long, requires = "control_stdin")]
+    external_clock: bool,
+
+    /// Serve a JSON API for reading and setting parameters at this address,
+    /// such as `127.0.0.1:8080`. See `GET /params`.
+    #[cfg(feature = "http-control")]
+    #[clap(long)]
+    http_control: Option<std::net::SocketAddr>,
+
+    /// Start from the beginning, instead of where the file was last stopped.
+    #[clap(long)]
+    no_resume: bool,
+
+    /// Playback speed, where 1.0 is normal speed.
+    #[clap(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Interpolation used when resampling to the device rate or changing speed.
+    #[clap(long, value_enum, default_value_t = Interpolation::Sinc)]
+    interpolation: Interpolation,
+
+    /// How the scope is drawn over the window contents.
+    ///
+    /// The window is cleared to black before each frame, so until something
+    /// else is drawn underneath the scope, all modes look the same.
+    #[clap(long, value_enum, default_value_t = BlendMode::Replace)]
+    blend: BlendMode,
+
+    /// Emulate the color and persistence of a CRT phosphor.
+    #[clap(long, value_enum)]
+    phosphor: Option<Phosphor>,
+
+    /// Color of the afterglow of the `custom` phosphor, as hex RGB.
+    #[clap(long, default_value = "ffa030", value_parser = parse_color)]
+    afterglow_color: [f32; 3],
+
+    /// Fraction of afterglow retained after each sample, for the `custom`
+    /// phosphor.
+    #[clap(long, default_value_t = 1.0 - 2e-5)]
+    afterglow_decay: f32,
+
+    /// Brightness of the afterglow of the `custom` phosphor, relative to the
+    /// beam.
+    #[clap(long, default_value_t = 0.05)]
+    afterglow_level: f32,
+
+    /// Fraction of intensity retained after each sample, at the center of the
+    /// screen.
+    #[clap(long)]
+    decay_center: Option<f32>,
+
+    /// Fraction of intensity retained after each sample, at the corners of the
+    /// screen. Defaults to the center decay.
+    #[clap(long)]
+    decay_edge: Option<f32>,
+
+    /// How quickly the decay changes from the center to the edge. Larger
+    /// values keep the center decay over a wider area.
+    #[clap(long, default_value_t = scope::DEFAULT_DECAY_FALLOFF)]
+    decay_falloff: f32,
+
+    /// Color the beam by the brightness of the sound: red for dull or
+    /// bass-heavy content, through yellow and green, to blue for bright
+    /// content.
+    #[clap(long)]
+    color_reactive: bool,
+
+    /// Report samples in the source that exceed full scale (±1.0).
+    #[clap(long)]
+    warn_clip: bool,
+
+    /// Keep the beam the same width on screen when zooming, instead of
+    /// widening it along with the signal.
+    #[clap(long)]
+    fixed_beam_width: bool,
+
+    /// Plot the left channel as a radius and the right channel as an angle,
+    /// instead of as X and Y.
+    #[clap(long)]
+    polar: bool,
+
+    /// In polar mode, the radius of the circle that the left channel's
+    /// minimum maps to. Its maximum maps to the edge of the screen.
+    #[clap(long, default_value_t = 0.2)]
+    polar_inner_radius: f32,
+
+    /// In polar mode, the number of turns around the circle across the right
+    /// channel's range.
+    #[clap(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    polar_turns: f32,
+
+    /// Rotate the image continuously, in degrees per second. Positive rates
+    /// turn counterclockwise.
+    #[clap(long, allow_negative_numbers = true)]
+    spin: Option<f32>,
+
+    /// Reflect the image across the vertical axis (`x`), the horizontal axis
+    /// (`y`), or both (`quad`).
+    #[clap(long, value_enum)]
+    mirror: Option<Mirror>,
+
+    /// Keep the overall brightness stable regardless of how many lines are
+    /// drawn each frame.
+    #[clap(long)]
+    normalize_intensity: bool,
+
+    /// Image to draw behind the beam, such as a graticule.
+    #[clap(long)]
+    background_image: Option<PathBuf>,
+
+    /// Opacity of the background image.
+    #[clap(long, default_value_t = 0.5)]
+    background_opacity: f32,
+
+    /// Rate at which points are fed to the scope, independent of the audio
+    /// sample rate. Decay is applied per point, so this also sets the time
+    /// scale of the trail.
+    #[clap(long, default_value_t = DEFAULT_VISUAL_RATE)]
+    visual_rate: u32,
+
+    /// Maximum amount of audio, in seconds, that can be waiting to be drawn.
+    /// If rendering stalls for longer than this, the oldest samples are dropped.
+    #[clap(long, default_value_t = 1.0)]
+    max_backlog: f32,
+
+    /// Split each rendered frame into this many persistence passes, in order.
+    /// Lines drawn earlier in the frame then decay before the later ones are
+    /// added, so fast motion fades along its path instead of landing all at
+    /// once.
+    #[clap(long, value_name = "K", default_value_t = 1)]
+    substeps: usize,
+
+    /// Accumulate the persistence buffer in a compute pass, which can be
+    /// faster for large windows.
+    #[clap(long)]
+    compute_persistence: bool,
+
+    /// Draw a slowly rotating figure while the input is silent, until the
+    /// signal returns.
+    #[clap(long)]
+    idle_pattern: bool,
+
+    /// Show the window title bar and borders (default).
+    #[clap(long, overrides_with = "no_decorations")]
+    decorations: bool,
+
+    /// Hide the window title bar and borders. The window can then be moved by
+    /// dragging anywhere inside it, and closed with Escape. While zoomed in,
+    /// dragging pans the view instead, so zoom back out to move the window.
+    #[clap(long, overrides_with = "decorations")]
+    no_decorations: bool,
+
+    /// Limit the frame rate while the window is unfocused or hidden, to save
+    /// power while still updating for screen capture.
+    #[clap(long, value_name = "FPS")]
+    background_fps: Option<f32>,
+
+    /// Add a little noise to the output, to hide banding in dark gradients.
+    #[clap(long)]
+    dither: bool,
+
+    /// Keep the window square while resizing.
+    #[clap(long)]
+    lock_aspect: bool,
+
+    /// Allow the window to be resized (default).
+    #[clap(long, overrides_with = "no_resizable")]
+    resizable: bool,
+
+    /// Prevent the window from being resized.
+    #[clap(long, overrides_with = "resizable")]
+    no_resizable: bool,
+
+    /// Render on a software adapter, such as lavapipe or WARP, instead of a GPU.
+    #[clap(long)]
+    software: bool,
 }
 
-pub type GraphicsContext = Arc<GraphicsContextInner>;
+#[derive(Debug, Clone, clap::Parser)]
+struct CaptureArgs {
+    path: PathBuf,
+
+    /// Timestamp of the captured frame, in seconds.
+    #[clap(long, default_value_t = 0.0)]
+    at: f32,
+
+    /// Output image path.
+    #[clap(long)]
+    out: PathBuf,
+
+    /// Also save the persistence buffer to an OpenEXR file, as linear floats.
+    #[clap(long)]
+    exr: Option<PathBuf>,
+
+    #[clap(long, default_value_t = 360)]
+    width: u32,
 
-type SampleBuf = Arc<ThingBuf<Vec<[f32; 2]>, WithCapacity>>;
+    #[clap(long, default_value_t = 360)]
+    height: u32,
 
-pub struct GraphicsContextInner {
-    pub surface: wgpu::Surface<'static>,
-    pub adapter: wgpu::Adapter,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub surface_caps: wgpu::SurfaceCapabilities,
-    pub surface_format: wgpu::TextureFormat,
-    pub window: Arc<Window>,
+    /// Fraction of intensity retained after each point.
+    #[clap(long)]
+    decay: Option<f32>,
+
+    /// Rate at which points are fed to the scope. See `play --help`.
+    #[clap(long, default_value_t = DEFAULT_VISUAL_RATE)]
+    visual_rate: u32,
+
+    /// Intensity falloff across the beam.
+    #[clap(long, value_enum, default_value_t = BeamProfile::Gaussian)]
+    beam_profile: BeamProfile,
+
+    /// Width of the beam profile, where the screen is 2.0 wide.
+    #[clap(long, default_value_t = 5e-3)]
+    beam_width: f32,
+
+    /// Keep the overall brightness stable regardless of how many lines are
+    /// drawn each frame.
+    #[clap(long)]
+    normalize_intensity: bool,
+
+    /// Draw the samples in frames as a display at this refresh rate would,
+    /// instead of as few frames as possible.
+    #[clap(long)]
+    frame_rate: Option<f32>,
+
+    /// Accumulate the persistence buffer in a compute pass, which can be
+    /// faster for large windows.
+    #[clap(long)]
+    compute_persistence: bool,
+
+    /// Render on a software adapter, such as lavapipe or WARP, instead of a GPU.
+    #[clap(long)]
+    software: bool,
 }
 
-impl GraphicsContextInner {
-    async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
-        let surface = instance
-            .create_surface(Arc::clone(&window))
-            .context("failed to create surface")?;
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .context("failed to create adapter")?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                    required_limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await?;
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+#[derive(Debug, Clone, clap::Parser)]
+struct ArtArgs {
+    path: PathBuf,
 
-        Ok(Self {
-            surface,
-            adapter,
-            device,
-            queue,
-            surface_caps,
-            surface_format,
-            window,
-        })
+    /// Save the image to a file, instead of showing it in a window.
+    #[clap(long)]
+    out: Option<PathBuf>,
+
+    #[clap(long, default_value_t = 720)]
+    width: u32,
+
+    #[clap(long, default_value_t = 720)]
+    height: u32,
+
+    /// Rate at which points are fed to the scope. See `play --help`.
+    #[clap(long, default_value_t = DEFAULT_VISUAL_RATE)]
+    visual_rate: u32,
+
+    /// Intensity falloff across the beam.
+    #[clap(long, value_enum, default_value_t = BeamProfile::Gaussian)]
+    beam_profile: BeamProfile,
+
+    /// Width of the beam profile, where the screen is 2.0 wide.
+    #[clap(long, default_value_t = 5e-3)]
+    beam_width: f32,
+
+    /// Render on a software adapter, such as lavapipe or WARP, instead of a GPU.
+    #[clap(long)]
+    software: bool,
+}
+
+#[derive(Debug, Clone, clap::Parser)]
+struct InfoArgs {
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ScopeTap {
+    /// The untouched source signal.
+    Raw,
+    /// The signal after all output processing.
+    Processed,
+}
+
+const DEFAULT_VISUAL_RATE: u32 = 48000;
+
+/// How often samples are taken from the audio thread while waiting for an
+/// external clock, or for the next frame while throttled.
+const EXTERNAL_CLOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Number of batches of processed frames that can wait for the next draw.
+const SCOPE_QUEUE_CAPACITY: usize = 64;
+
+/// Parse an sRGB hex color, such as `ffa030`, into linear RGB.
+fn parse_color(s: &str) -> Result<[f32; 3], String> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    let value = u32::from_str_radix(s, 16)
+        .ok()
+        .filter(|_| s.len() == 6)
+        .ok_or_else(|| format!("invalid color {s:?}, expected RRGGBB"))?;
+    Ok([16, 8, 0].map(|shift| {
+        let c = ((value >> shift) & 0xff) as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }))
+}
+
+/// Pass processed frames to the scope through its source queue.
+fn submit(queue: &SampleQueue, scope: &mut Scope, frames: &mut Vec<[f32; 2]>) {
+    if frames.is_empty() {
+        return;
     }
+    if queue.push(frames.iter().copied()).is_err() {
+        // Many updates went by without a draw, as while throttled. Hand the
+        // waiting batches over early, so that nothing is dropped.
+        debug!(
+            "scope queue full at {} batches, draining early",
+            queue.capacity()
+        );
+        scope.drain_source();
+        scope.extend(frames.iter().copied());
+    }
+    frames.clear();
 }
 
 struct App {
     gfx: GraphicsContext,
-    sample_buf: SampleBuf,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_caps: wgpu::SurfaceCapabilities,
+    surface_format: wgpu::TextureFormat,
+    sample_queue: SampleQueue,
+    /// Processed frames, drained by the scope when it draws.
+    scope_queue: SampleQueue,
     scope: Scope,
+    paused: Arc<AtomicBool>,
+    /// Time to move the source to, in seconds, taken by the audio thread.
+    seek: Arc<Mutex<Option<f32>>>,
+    #[cfg(feature = "media-keys")]
+    media: Option<Media>,
+    #[cfg(feature = "http-control")]
+    shared_params: Option<SharedParams>,
+    display: DisplayChain,
+    display_frames: Vec<[f32; 2]>,
+    reactive_color: Option<ReactiveColor>,
+    idle: Option<IdlePattern>,
+    align: Option<PeriodDetector>,
+
+    modifiers: ModifiersState,
+    cursor: PhysicalPosition<f64>,
+    dragging: bool,
+
+    lock_aspect: bool,
+    last_size: PhysicalSize<u32>,
+    /// Square size requested to correct the aspect ratio, not yet applied.
+    aspect_request: Option<PhysicalSize<u32>>,
 
     fps_start: Instant,
     fps_count: usize,
+    /// Draw the number of lines in each chunk over the scope (F3).
+    show_occupancy: bool,
+
+    background_fps: Option<f32>,
+    external_clock: bool,
+    focused: bool,
+    occluded: bool,
+    last_redraw: Instant,
+
+    warn_clip: bool,
+    /// Number of frames beyond full scale since the last report.
+    clipped: usize,
 }
 
 impl App {
-    async fn new(window: Window, sample_buf: SampleBuf) -> anyhow::Result<Self> {
-        let gfx = Arc::new(GraphicsContextInner::new(Arc::new(window)).await?);
-        let scope = Scope::new(Arc::clone(&gfx));
+    async fn new(
+        window: Window,
+        sample_queue: SampleQueue,
+        sample_rate: u32,
+        paused: Arc<AtomicBool>,
+        seek: Arc<Mutex<Option<f32>>>,
+        args: &PlayArgs,
+    ) -> anyhow::Result<Self> {
+        let window = Arc::new(window);
+        let instance = GraphicsContextInner::create_instance();
+        let surface = GraphicsContextInner::create_surface(&instance, &window)?;
+        let gfx =
+            Arc::new(GraphicsContextInner::new(&instance, Some(&surface), args.software).await?);
 
-        Ok(Self {
+        let surface_caps = surface.get_capabilities(&gfx.adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+
+        let size = window.inner_size();
+        let mut scope = Scope::new(Arc::clone(&gfx), surface_format, size.into());
+        let scope_queue = SampleQueue::new(SCOPE_QUEUE_CAPACITY);
+        scope.set_source(Some(scope_queue.clone()));
+        scope.set_beam(args.beam_profile, args.beam_width);
+        scope.set_blend_mode(args.blend);
+        scope.set_mirror(args.mirror);
+        scope.set_polar(args.polar.then_some(Polar {
+            inner_radius: args.polar_inner_radius,
+            turns: args.polar_turns,
+        }));
+        if let Some(spin) = args.spin {
+            scope.set_spin(f64::from(spin).to_radians() / f64::from(args.visual_rate));
+        }
+        scope.set_normalize_intensity(args.normalize_intensity);
+        scope.set_beam_scales_with_zoom(!args.fixed_beam_width);
+        if let Some(phosphor) = args.phosphor {
+            if let Some(color) = phosphor.beam_color() {
+                scope.set_beam_color(color);
+            }
+            if let Some(decay) = phosphor.decay() {
+                scope.set_decay(decay);
+            }
+            scope.set_afterglow(match phosphor {
+                Phosphor::Custom => Some(Afterglow {
+                    color: args.afterglow_color,
+                    decay: args.afterglow_decay,
+                    level: args.afterglow_level,
+                }),
+                _ => phosphor.afterglow(),
+            });
+        }
+        if args.decay_center.is_some() || args.decay_edge.is_some() {
+            let center = args.decay_center.unwrap_or(scope.decay());
+            let edge = args.decay_edge.unwrap_or(center);
+            scope.set_spatial_decay(center, edge, args.decay_falloff);
+        }
+        scope.set_max_retained((args.max_backlog * args.visual_rate as f32) as usize);
+        scope.set_substeps(args.substeps);
+        scope.set_compute_persistence(args.compute_persistence);
+        if args.dither {
+            scope.set_dither(1.0);
+        }
+        if let Some(path) = &args.background_image {
+            let image = image::open(path)
+                .with_context(|| format!("failed to open background image {}", path.display()))?;
+            scope.set_background(&image.into_rgba8(), args.background_opacity);
+        }
+
+        let mut app = Self {
             gfx,
-            sample_buf,
+            window,
+            surface,
+            surface_caps,
+            surface_format,
+            sample_queue,
+            scope_queue,
             scope,
+            paused,
+            seek,
+            #[cfg(feature = "media-keys")]
+            media: None,
+            #[cfg(feature = "http-control")]
+            shared_params: None,
+            display: DisplayChain {
+                rumble: args
+                    .rumble_filter
+                    .map(|corner| HighPass::new(corner, sample_rate)),
+                tilt: args.tilt.map(|tilt| Tilt::new(tilt, sample_rate)),
+                decorrelate: args.decorrelate.then(Decorrelate::new),
+                resample: Resample::new(sample_rate, args.visual_rate),
+            },
+            display_frames: Vec::new(),
+            reactive_color: args.color_reactive.then(|| ReactiveColor::new(sample_rate)),
+            align: args
+                .auto_align
+                .map(|threshold| PeriodDetector::new(threshold, args.visual_rate)),
+            idle: args
+                .idle_pattern
+                .then(|| IdlePattern::new(args.visual_rate)),
+            modifiers: ModifiersState::empty(),
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            dragging: false,
+            lock_aspect: args.lock_aspect,
+            last_size: size,
+            aspect_request: None,
             fps_start: Instant::now(),
             fps_count: 0,
-        })
+            show_occupancy: false,
+            background_fps: args.background_fps.filter(|&fps| fps > 0.0),
+            external_clock: args.external_clock,
+            focused: true,
+            occluded: false,
+            last_redraw: Instant::now(),
+            warn_clip: args.warn_clip,
+            clipped: 0,
+        };
+        app.reconfigure();
+        app.scope.warm_up();
+        Ok(app)
     }
 
     fn update(&mut self) {
-        loop {
-            let result = self.sample_buf.pop_with(|frames| {
-                self.scope.extend(frames.iter().copied());
-            });
-            if result.is_none() {
-                break;
+        self.sample_queue.drain(|frames| {
+            if self.warn_clip {
+                self.clipped += frames
+                    .iter()
+                    .filter(|frame| frame.iter().any(|x| x.abs() > 1.0))
+                    .count();
             }
+            if let Some(reactive_color) = &mut self.reactive_color {
+                reactive_color.process(frames);
+            }
+            if let Some(idle) = &mut self.idle {
+                idle.observe(frames);
+                if idle.is_active() {
+                    // The silence would only draw a dot at the origin, with
+                    // lines out to the pattern on every frame.
+                    return;
+                }
+            }
+            self.display.process(frames, &mut self.display_frames);
+            if let Some(align) = &mut self.align {
+                align.process(&self.display_frames);
+            }
+            submit(&self.scope_queue, &mut self.scope, &mut self.display_frames);
+        });
+        if let Some(align) = &self.align {
+            self.scope.set_alignment(align.period());
+        }
+        if let Some(idle) = &mut self.idle {
+            // The pattern is already at the visual rate, so it skips the display chain.
+            if !self.paused.load(Ordering::Relaxed) {
+                idle.generate(&mut self.display_frames);
+                submit(&self.scope_queue, &mut self.scope, &mut self.display_frames);
+            }
+        }
+        if let Some(reactive_color) = &self.reactive_color {
+            self.scope.set_beam_color(reactive_color.color());
+        }
+        #[cfg(feature = "http-control")]
+        if let Some(shared_params) = &self.shared_params {
+            let view = self.scope.view();
+            *shared_params.lock().unwrap() = Params {
+                paused: self.paused.load(Ordering::Relaxed),
+                frozen: self.scope.frozen(),
+                decay: self.scope.decay(),
+                zoom: view.zoom,
+                beam_color: self.scope.beam_color(),
+                intensity: self.scope.intensity(),
+                center: view.center.into(),
+                blend: self.scope.blend_mode(),
+            };
         }
     }
 
     fn redraw(&mut self) -> anyhow::Result<()> {
         let frame = loop {
-            match self.gfx.surface.get_current_texture() {
+            match self.surface.get_current_texture() {
                 Ok(frame) => break frame,
                 Err(wgpu::SurfaceError::Lost) => {
                     self.reconfigure();
@@ -148,17 +718,65 @@ impl App {
         let frame_view = frame.texture.create_view(&Default::default());
         let mut encoder = self.gfx.device.create_command_encoder(&Default::default());
 
+        if self.scope.blend_mode() != BlendMode::Replace {
+            // The scope composites over the existing contents, which for a
+            // freshly acquired surface texture are undefined.
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("App.clear"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+        }
         self.scope.draw(&frame_view, &mut encoder, &self.gfx.queue);
+        if self.show_occupancy {
+            self.scope.draw_occupancy(&frame_view, &mut encoder);
+        }
 
         self.gfx.queue.submit([encoder.finish()]);
         frame.present();
 
         self.fps_count += 1;
+        self.last_redraw = Instant::now();
 
         let elapsed = self.fps_start.elapsed();
         if elapsed > Duration::from_secs(1) {
             let fps = self.fps_count as f32 / elapsed.as_secs_f32();
-            println!("FPS: {fps:8.1}");
+            let metrics = self.scope.take_metrics();
+            debug!(
+                "FPS: {fps:8.1}, {} samples in, {} dropped, last frame {} lines over {:.0} samples",
+                metrics.pushed, metrics.dropped, metrics.lines, metrics.total_time,
+            );
+            if metrics.overruns > 0 {
+                warn!(
+                    "OVERRUN from audio thread: {} frames lost in {} overruns in the last {:.1} s",
+                    metrics.overrun_frames,
+                    metrics.overruns,
+                    elapsed.as_secs_f32(),
+                );
+            }
+            if metrics.dropped > 0 {
+                warn!(
+                    "scope fell behind, dropped {} samples in the last {:.1} s",
+                    metrics.dropped,
+                    elapsed.as_secs_f32(),
+                );
+            }
+            if self.show_occupancy {
+                info!(
+                    "{} lines, up to {} in one chunk",
+                    metrics.lines, metrics.busiest_chunk
+                );
+            }
+            if self.warn_clip {
+                self.report_clipping();
+            }
 
             self.fps_start = Instant::now();
             self.fps_count = 0;
@@ -167,144 +785,570 @@ impl App {
         Ok(())
     }
 
+    /// While the window is in the background, the time before which the next
+    /// frame shouldn't be drawn.
+    fn throttle_deadline(&self) -> Option<Instant> {
+        let fps = self.background_fps?;
+        if self.focused && !self.occluded {
+            return None;
+        }
+        let deadline = self.last_redraw + Duration::from_secs_f32(1.0 / fps);
+        (Instant::now() < deadline).then_some(deadline)
+    }
+
+    /// Whether the frame rate is limited to `background_fps`.
+    fn throttled(&self) -> bool {
+        self.background_fps.is_some() && !(self.focused && !self.occluded)
+    }
+
+    fn set_visibility(&mut self, focused: bool, occluded: bool) {
+        let throttled = self.throttled();
+        self.focused = focused;
+        self.occluded = occluded;
+        if self.throttled() != throttled {
+            // The average samples per frame at the old frame rate would
+            // leave the scope drawing too much or too little.
+            self.scope.reset_pacing();
+        }
+    }
+
+    fn report_clipping(&mut self) {
+        if self.clipped > 0 {
+            warn!("CLIPPING: {} frames exceeded full scale", self.clipped);
+            self.window.set_title("Glowie - CLIPPING");
+        } else {
+            self.window.set_title("Glowie");
+        }
+        self.clipped = 0;
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        #[cfg(feature = "media-keys")]
+        if let Some(media) = &mut self.media {
+            media.set_paused(paused);
+        }
+    }
+
+    /// Returns false if the app should exit.
+    #[cfg(feature = "media-keys")]
+    fn media_event(&mut self, event: MediaControlEvent) -> bool {
+        if let Some(paused) = media::apply_pause(&event, self.paused.load(Ordering::Relaxed)) {
+            self.set_paused(paused);
+        }
+        match event {
+            MediaControlEvent::Stop | MediaControlEvent::Quit => false,
+            // Only one file is played, so there's nothing to skip to.
+            MediaControlEvent::Next | MediaControlEvent::Previous => {
+                debug!("ignoring {event:?}: there is no playlist");
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns false if the app should exit.
+    fn control(&mut self, command: ControlCommand) -> bool {
+        match command {
+            ControlCommand::Pause => self.set_paused(true),
+            ControlCommand::Play => self.set_paused(false),
+            ControlCommand::Toggle => self.set_paused(!self.paused.load(Ordering::Relaxed)),
+            ControlCommand::Freeze(frozen) => self.scope.set_frozen(frozen),
+            ControlCommand::SetDecay(decay) => self.scope.set_decay(decay),
+            ControlCommand::Zoom(factor) => self.scope.zoom_by(factor),
+            ControlCommand::SetZoom(zoom) => self.scope.set_zoom(zoom),
+            ControlCommand::SetColor(color) => self.scope.set_beam_color(color),
+            ControlCommand::SetIntensity(intensity) => self.scope.set_intensity(intensity),
+            ControlCommand::SetCenter(center) => self.scope.set_center(center.into()),
+            ControlCommand::SetBlend(mode) => self.scope.set_blend_mode(mode),
+            ControlCommand::Seek(seconds) => *self.seek.lock().unwrap() = Some(seconds),
+            // Drawn by the event loop.
+            ControlCommand::Frame => {}
+            ControlCommand::Quit => return false,
+        }
+        true
+    }
+
+    /// Returns false if the app should exit.
+    fn key_pressed(&mut self, key: &Key) -> bool {
+        // Cmd on macOS, Ctrl elsewhere.
+        let command = if cfg!(target_os = "macos") {
+            self.modifiers.super_key()
+        } else {
+            self.modifiers.control_key()
+        };
+
+        match key.as_ref() {
+            Key::Named(NamedKey::Escape) => return false,
+            Key::Character("q") if command => return false,
+            Key::Character("f") => {
+                self.scope.set_frozen(!self.scope.frozen());
+            }
+            Key::Character("e") => self.save_exr(),
+            Key::Character("h") => {
+                self.scope.set_held(!self.scope.held());
+            }
+            Key::Named(NamedKey::F3) => {
+                self.show_occupancy = !self.show_occupancy;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Save the persistence buffer to a timestamped file in the working directory.
+    fn save_exr(&self) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = PathBuf::from(format!("glowie-{timestamp}.exr"));
+        match headless::save_exr(&self.gfx, &self.scope, &path) {
+            Ok(()) => info!("saved {}", path.display()),
+            Err(e) => error!("{e:#}"),
+        }
+    }
+
+    /// Called once when the event loop exits, for any reason.
+    fn shutdown(&mut self) {
+        self.paused.store(true, Ordering::Relaxed);
+        #[cfg(feature = "media-keys")]
+        if let Some(media) = &mut self.media {
+            media.set_stopped();
+        }
+    }
+
+    fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        if self.dragging {
+            let size = self.window.inner_size();
+            // The scope occupies a centered square, 2 units wide.
+            let scale = 2.0 / size.width.min(size.height).max(1) as f32;
+            let delta = Vec2::new(
+                (position.x - self.cursor.x) as f32,
+                -(position.y - self.cursor.y) as f32,
+            );
+            self.scope.pan(delta * scale);
+        }
+        self.cursor = position;
+    }
+
+    fn mouse_input(&mut self, state: ElementState, button: MouseButton) {
+        if button == MouseButton::Left {
+            let window = &self.window;
+            if state == ElementState::Pressed
+                && !window.is_decorated()
+                && self.scope.view().zoom <= 1.0
+            {
+                // Without a title bar, the window is moved by dragging its
+                // contents, unless zoomed in, where dragging pans the view.
+                if let Err(e) = window.drag_window() {
+                    warn!("failed to drag window: {e}");
+                }
+                return;
+            }
+            self.dragging = state == ElementState::Pressed;
+        }
+    }
+
+    fn mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+        };
+        self.scope.zoom_by(1.1f32.powf(lines));
+    }
+
     fn window_resized(&mut self) {
-        self.scope.window_resized();
+        let size = self.window.inner_size();
+        if self.lock_aspect {
+            self.snap_to_square(size);
+        }
+        self.last_size = size;
+        self.scope.resize(size.into());
         self.reconfigure();
     }
 
+    /// Ask for a square window, following whichever side the user dragged.
+    fn snap_to_square(&mut self, size: PhysicalSize<u32>) {
+        if size.width == size.height {
+            self.aspect_request = None;
+            return;
+        }
+        if self.aspect_request.take().is_some() {
+            // The last request wasn't honored exactly, e.g. by a tiling window
+            // manager. Accept the size instead of fighting over it.
+            return;
+        }
+        let side = if size.width.abs_diff(self.last_size.width)
+            >= size.height.abs_diff(self.last_size.height)
+        {
+            size.width
+        } else {
+            size.height
+        };
+        let request = PhysicalSize::new(side, side);
+        self.aspect_request = Some(request);
+        // Applied either immediately or by a later resize event.
+        let _ = self.window.request_inner_size(request);
+    }
+
+    /// Configure the surface to the scope's size, so that they always match,
+    /// even if the window has been resized again since the scope was.
     fn reconfigure(&self) {
-        let size = self.gfx.window.inner_size();
+        let [width, height] = self.scope.size();
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: self.gfx.surface_format,
-            width: size.width,
-            height: size.height,
+            format: self.surface_format,
+            width,
+            height,
             present_mode: wgpu::PresentMode::AutoVsync,
             desired_maximum_frame_latency: 1,
-            alpha_mode: self.gfx.surface_caps.alpha_modes[0],
+            alpha_mode: self.surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
-        self.gfx.surface.configure(&self.gfx.device, &config);
+        self.surface.configure(&self.gfx.device, &config);
     }
 }
 
-enum AppEvent {
-    Overrun,
+pub enum AppEvent {
+    /// The scope fell behind, and the audio thread dropped frames.
+    Overrun {
+        dropped: usize,
+        at: Instant,
+    },
+    #[cfg(feature = "media-keys")]
+    Media(MediaControlEvent),
+    Control(ControlCommand),
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::init();
-
-    // Open audio file
     let args = Args::parse();
-    let mut source = match args.command {
-        Command::Play(play_args) => {
-            let file = audrey::open(play_args.path)?;
+    let level = args.log_level();
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Error))
+        .filter_module("glowie", level)
+        .parse_default_env()
+        .init();
 
-            file
+    match args.command {
+        Command::Play(play_args) => play(play_args),
+        Command::Capture(capture_args) => capture::capture(capture_args),
+        Command::Art(art_args) => art::art(art_args),
+        Command::Info(info_args) => info::info(info_args),
+    }
+}
+
+fn play(play_args: PlayArgs) -> anyhow::Result<()> {
+    // Open audio file
+    let mut source = FileSource::open(&play_args.path)?;
+    let title = play_args
+        .path
+        .file_name()
+        .unwrap_or(play_args.path.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+    let resume = if play_args.no_resume {
+        None
+    } else {
+        Resume::new(&play_args.path)
+    };
+    let source: Box<dyn SampleSource> = match &resume {
+        Some(resume) => {
+            resume.seek(&mut source);
+            Box::new(resume.track(source))
         }
+        None => Box::new(source),
     };
-    let descr = source.description();
-    ensure!(
-        descr.channel_count() == 2,
-        "audio channels must be equal to 2 (stereo)"
-    );
 
-    let target_channels = ChannelCount::try_from(descr.channel_count()).unwrap();
-    let target_rate = SampleRate(descr.sample_rate());
+    play_source(source, &title, play_args)?;
+    if let Some(resume) = resume {
+        resume.save();
+    }
+    Ok(())
+}
+
+/// Play any source of stereo frames, while showing it on the scope.
+fn play_source(
+    mut source: Box<dyn SampleSource>,
+    // Only shown in the OS media session.
+    #[cfg_attr(not(feature = "media-keys"), allow(unused_variables))]
+    title: &str,
+    play_args: PlayArgs,
+) -> anyhow::Result<()> {
+    let target_channels: ChannelCount = 2;
+    let target_rate = SampleRate(source.sample_rate());
 
     // Setup audio output
-    let host = cpal::default_host();
-    let output_device = host
-        .default_output_device()
-        .context("no default output device")?;
-    let output_config = output_device
-        .supported_output_configs()?
-        .filter(|cfg| cfg.channels() == target_channels)
-        .filter_map(|cfg| cfg.try_with_sample_rate(target_rate))
-        .max_by_key(|config| {
-            // Priorities:
-            // - Floating-point input
-            // - Maximum precision
-            // - Maximum buffer size
-            (
-                config.sample_format().is_float(),
-                config.sample_format().sample_size(),
-                match *config.buffer_size() {
-                    SupportedBufferSize::Range { max, .. } => max,
-                    _ => 0,
-                },
-            )
-        })
-        .context("no device configuration matches the given sample rate and channel count")?;
+    let output = if play_args.no_audio {
+        None
+    } else {
+        let host = cpal::default_host();
+        let output_device = host
+            .default_output_device()
+            .context("no default output device")?;
+        let output_config =
+            audio::select_output_config(&output_device, target_channels, target_rate)?;
+        info!(
+            "output: {} at {} Hz, {}",
+            output_device
+                .name()
+                .unwrap_or_else(|_| "unknown device".into()),
+            output_config.sample_rate().0,
+            output_config.sample_format(),
+        );
+        Some((output_device, output_config))
+    };
+    let output_rate = match &output {
+        Some((_, output_config)) => output_config.sample_rate(),
+        None => target_rate,
+    };
+
+    ensure!(
+        play_args.speed.is_finite() && play_args.speed > 0.0,
+        "speed must be positive"
+    );
+    let mut resampler = (output_rate != target_rate || play_args.speed != 1.0).then(|| {
+        Resampler::new(
+            target_rate.0,
+            output_rate.0,
+            play_args.speed,
+            play_args.interpolation,
+        )
+    });
+    if output_rate != target_rate {
+        if output_rate < target_rate {
+            warn!(
+                "file sample rate {} Hz exceeds the device maximum of {} Hz; \
+                downsampling",
+                target_rate.0, output_rate.0,
+            );
+            if play_args.interpolation == Interpolation::Sinc {
+                warn!(
+                    "content above {} Hz will be filtered out",
+                    output_rate.0 / 2
+                );
+            } else {
+                warn!("content above {} Hz may alias", output_rate.0 / 2);
+            }
+        } else {
+            info!(
+                "resampling from {} Hz to {} Hz",
+                target_rate.0, output_rate.0
+            );
+        }
+    }
 
     let event_loop = EventLoopBuilder::<AppEvent>::with_user_event().build()?;
-    let sample_buf: SampleBuf = Arc::new(ThingBuf::with_recycle(64, WithCapacity::new()));
+    let sample_queue = SampleQueue::new(64);
 
-    let audio_buf = Arc::clone(&sample_buf);
+    let paused = Arc::new(AtomicBool::new(false));
+
+    let audio_queue = sample_queue.clone();
     let audio_events = event_loop.create_proxy();
-    let output_stream = output_device.build_output_stream::<f32, _, _>(
-        &output_config.config(),
-        move |output_data, _output_info| {
-            let in_frames = source
-                .frames::<[f32; 2]>()
-                .map(|result| result.expect("read error"))
-                .chain(repeat([0.0; 2]));
-            let out_frames = output_data.chunks_mut(2);
-
-            for (in_frame, out_frame) in in_frames.zip(out_frames) {
-                out_frame.copy_from_slice(&in_frame);
-            }
-
-            let push_result = audio_buf.push_with(|frames| {
-                frames.clear();
-                frames.extend(
-                    output_data
-                        .chunks(2)
-                        .map(|v| <[f32; 2]>::try_from(v).unwrap()),
-                );
-            });
-            if push_result.is_err() {
-                let _ = audio_events.send_event(AppEvent::Overrun);
+    let audio_paused = Arc::clone(&paused);
+    let seek = Arc::new(Mutex::new(None));
+    let audio_seek = Arc::clone(&seek);
+    let scope_tap = play_args.scope_tap;
+    let mut raw_frames: Vec<[f32; 2]> = Vec::new();
+    let render = move |output_data: &mut [f32]| {
+        if audio_paused.load(Ordering::Relaxed) {
+            output_data.fill(0.0);
+            return;
+        }
+        // A request that arrives while the lock is held waits for the next call.
+        if let Some(seconds) = audio_seek.try_lock().ok().and_then(|mut seek| seek.take()) {
+            let frame = (f64::from(seconds) * f64::from(source.sample_rate())) as u64;
+            if let Err(e) = source.seek(frame) {
+                warn!("cannot seek: {e:#}");
             }
-        },
-        |stream_error| {
-            eprintln!("stream error: {:?}", stream_error);
-        },
-        None,
-    )?;
-    output_stream.play()?;
+        }
+
+        raw_frames.clear();
+        {
+            let mut in_frames =
+                source::frames(&mut source).inspect(|&frame| raw_frames.push(frame));
+            let mut next_frame = || match &mut resampler {
+                Some(resampler) => resampler.next(&mut in_frames),
+                None => in_frames.next(),
+            };
+            for out_frame in output_data.chunks_mut(2) {
+                out_frame.copy_from_slice(&next_frame().unwrap_or([0.0; 2]));
+            }
+        }
+
+        let push_result = audio_queue.try_reserve().map(|mut frames| match scope_tap {
+            ScopeTap::Raw => frames.extend_from_slice(&raw_frames),
+            ScopeTap::Processed => frames.extend(
+                output_data
+                    .chunks(2)
+                    .map(|v| <[f32; 2]>::try_from(v).unwrap()),
+            ),
+        });
+        if push_result.is_err() {
+            let dropped = match scope_tap {
+                ScopeTap::Raw => raw_frames.len(),
+                ScopeTap::Processed => output_data.len() / 2,
+            };
+            let _ = audio_events.send_event(AppEvent::Overrun {
+                dropped,
+                at: Instant::now(),
+            });
+        }
+    };
+
+    // Keep the stream alive until the event loop exits.
+    let _output_stream = match &output {
+        Some((output_device, output_config)) => {
+            let output_stream = audio::build_output_stream(output_device, output_config, render)?;
+            output_stream.play()?;
+            Some(output_stream)
+        }
+        None => {
+            audio::spawn_clock(output_rate, render);
+            None
+        }
+    };
 
     // Setup graphics loop
     // TODO account for sample rate in graphics
     let window = WindowBuilder::new()
         .with_inner_size(LogicalSize::new(360, 360))
         .with_title("Glowie")
-        .with_decorations(false)
+        .with_decorations(play_args.decorations || !play_args.no_decorations)
+        .with_resizable(play_args.resizable || !play_args.no_resizable)
         .build(&event_loop)?;
 
-    let mut app = block_on(App::new(window, sample_buf))?;
-    app.reconfigure();
+    #[cfg(feature = "media-keys")]
+    let media = match Media::new(&window, event_loop.create_proxy()) {
+        Ok(mut media) => {
+            media.set_title(title);
+            media.set_paused(false);
+            Some(media)
+        }
+        Err(e) => {
+            warn!("media keys unavailable: {e:#}");
+            None
+        }
+    };
+    if play_args.control_stdin {
+        control::spawn_stdin(event_loop.create_proxy());
+    }
+    #[cfg(feature = "http-control")]
+    let shared_params = match play_args.http_control {
+        Some(addr) => {
+            let params = SharedParams::default();
+            http::spawn(addr, Arc::clone(&params), event_loop.create_proxy())?;
+            Some(params)
+        }
+        None => None,
+    };
+
+    let scope_rate = match play_args.scope_tap {
+        ScopeTap::Raw => SampleRate((target_rate.0 as f64 * play_args.speed) as u32),
+        ScopeTap::Processed => output_rate,
+    };
+    let mut app = block_on(App::new(
+        window,
+        sample_queue,
+        scope_rate.0,
+        paused,
+        seek,
+        &play_args,
+    ))?;
+    #[cfg(feature = "media-keys")]
+    {
+        app.media = media;
+    }
+    #[cfg(feature = "http-control")]
+    {
+        app.shared_params = shared_params;
+    }
 
     event_loop.set_control_flow(ControlFlow::Poll);
 
     event_loop.run(move |event, elwt| match event {
-        Event::AboutToWait => {
+        Event::AboutToWait if app.external_clock => {
+            // Keep taking samples from the audio thread between ticks.
             app.update();
-            app.redraw().unwrap();
+            elwt.set_control_flow(ControlFlow::WaitUntil(
+                Instant::now() + EXTERNAL_CLOCK_POLL_INTERVAL,
+            ));
         }
+        Event::AboutToWait => match app.throttle_deadline() {
+            Some(deadline) => {
+                // Keep taking samples from the audio thread between frames.
+                app.update();
+                elwt.set_control_flow(ControlFlow::WaitUntil(
+                    deadline.min(Instant::now() + EXTERNAL_CLOCK_POLL_INTERVAL),
+                ));
+            }
+            None => {
+                elwt.set_control_flow(ControlFlow::Poll);
+                app.update();
+                app.redraw().unwrap();
+            }
+        },
         Event::WindowEvent { event, .. } => match event {
             WindowEvent::CloseRequested => {
                 elwt.exit();
             }
+            WindowEvent::Focused(focused) => {
+                app.set_visibility(focused, app.occluded);
+            }
+            WindowEvent::Occluded(occluded) => {
+                app.set_visibility(app.focused, occluded);
+            }
             WindowEvent::Resized(..) | WindowEvent::ScaleFactorChanged { .. } => {
                 app.window_resized();
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                app.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.state == ElementState::Pressed
+                    && !event.repeat
+                    && !app.key_pressed(&event.logical_key)
+                {
+                    elwt.exit();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                app.cursor_moved(position);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                app.mouse_input(state, button);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                app.mouse_wheel(delta);
+            }
             _ => {}
         },
+        Event::LoopExiting => {
+            app.shutdown();
+        }
         Event::UserEvent(app_event) => match app_event {
-            AppEvent::Overrun => {
-                eprintln!("OVERRUN from audio thread");
+            AppEvent::Overrun { dropped, at } => {
+                app.scope.record_overrun(dropped);
+                // Summarized once per second by `redraw`.
+                debug!(
+                    "OVERRUN from audio thread: {dropped} frames dropped {:.1} ms ago",
+                    at.elapsed().as_secs_f64() * 1000.0,
+                );
+            }
+            #[cfg(feature = "media-keys")]
+            AppEvent::Media(media_event) => {
+                if !app.media_event(media_event) {
+                    elwt.exit();
+                }
+            }
+            AppEvent::Control(ControlCommand::Frame) if app.external_clock => {
+                app.update();
+                app.redraw().unwrap();
+            }
+            AppEvent::Control(command) => {
+                if !app.control(command) {
+                    elwt.exit();
+                }
             }
         },
         _ => {}
@@ -0,0 +1,86 @@
+use anyhow::anyhow;
+use log::warn;
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+use winit::event_loop::EventLoopProxy;
+use winit::window::Window;
+
+use crate::AppEvent;
+
+/// Connection to the OS media session (MPRIS, SMTC, MPNowPlayingInfoCenter).
+///
+/// Media key presses are forwarded to the event loop as [`AppEvent::Media`].
+/// Play, pause, stop and quit are supported. Next and previous are ignored,
+/// since only one file is played.
+pub struct Media {
+    controls: MediaControls,
+}
+
+impl Media {
+    pub fn new(window: &Window, events: EventLoopProxy<AppEvent>) -> anyhow::Result<Self> {
+        let config = PlatformConfig {
+            dbus_name: "glowie",
+            display_name: "Glowie",
+            hwnd: hwnd(window),
+        };
+        let mut controls =
+            MediaControls::new(config).map_err(|e| anyhow!("media controls: {e:?}"))?;
+        controls
+            .attach(move |event| {
+                let _ = events.send_event(AppEvent::Media(event));
+            })
+            .map_err(|e| anyhow!("media controls: {e:?}"))?;
+        Ok(Self { controls })
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        let result = self.controls.set_metadata(MediaMetadata {
+            title: Some(title),
+            ..Default::default()
+        });
+        if let Err(e) = result {
+            warn!("failed to set media metadata: {e:?}");
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        let playback = if paused {
+            MediaPlayback::Paused { progress: None }
+        } else {
+            MediaPlayback::Playing { progress: None }
+        };
+        if let Err(e) = self.controls.set_playback(playback) {
+            warn!("failed to set media playback state: {e:?}");
+        }
+    }
+
+    pub fn set_stopped(&mut self) {
+        if let Err(e) = self.controls.set_playback(MediaPlayback::Stopped) {
+            warn!("failed to set media playback state: {e:?}");
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn hwnd(window: &Window) -> Option<*mut std::ffi::c_void> {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    match window.window_handle().ok()?.as_raw() {
+        RawWindowHandle::Win32(handle) => Some(handle.hwnd.get() as *mut std::ffi::c_void),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn hwnd(_window: &Window) -> Option<*mut std::ffi::c_void> {
+    None
+}
+
+/// Whether an event is meant for the transport, and how it changes the paused state.
+pub fn apply_pause(event: &MediaControlEvent, paused: bool) -> Option<bool> {
+    match event {
+        MediaControlEvent::Play => Some(false),
+        MediaControlEvent::Pause => Some(true),
+        MediaControlEvent::Toggle => Some(!paused),
+        _ => None,
+    }
+}
@@ -0,0 +1,51 @@
+//! Lock-free handoff of samples from a producer, usually an audio callback,
+//! to the thread that draws them.
+
+use std::sync::Arc;
+use thingbuf::recycling::WithCapacity;
+use thingbuf::{Full, Ref, ThingBuf};
+
+/// A bounded queue of sample batches.
+///
+/// Batches are recycled, so once the queue has warmed up, pushing doesn't
+/// allocate. Cloning gives another handle to the same queue.
+#[derive(Clone)]
+pub struct SampleQueue {
+    buf: Arc<ThingBuf<Vec<[f32; 2]>, WithCapacity>>,
+}
+
+impl SampleQueue {
+    /// Create a queue holding up to `capacity` batches.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: Arc::new(ThingBuf::with_recycle(capacity, WithCapacity::new())),
+        }
+    }
+
+    /// Maximum number of batches that can be waiting.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Push a batch of samples, or return `Err` if the queue is full and the
+    /// samples were dropped.
+    pub fn push(&self, frames: impl IntoIterator<Item = [f32; 2]>) -> Result<(), Full> {
+        self.buf.push_with(|batch| {
+            batch.clear();
+            batch.extend(frames);
+        })
+    }
+
+    /// Reserve a slot for a batch, to be filled in place. The batch is queued
+    /// when the returned reference is dropped.
+    pub fn try_reserve(&self) -> Result<Ref<'_, Vec<[f32; 2]>>, Full> {
+        let mut batch = self.buf.push_ref()?;
+        batch.clear();
+        Ok(batch)
+    }
+
+    /// Pass each waiting batch to `f`, in order.
+    pub fn drain(&self, mut f: impl FnMut(&[[f32; 2]])) {
+        while self.buf.pop_with(|batch| f(batch)).is_some() {}
+    }
+}
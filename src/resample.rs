@@ -0,0 +1,238 @@
+//! Sample rate conversion and speed change for the audio output.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// Number of zero crossings of the interpolation kernel on each side.
+const ZERO_CROSSINGS: f64 = 16.0;
+
+/// Method for computing samples between input samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Interpolation {
+    /// Straight lines between samples. Cheap, but dulls high frequencies.
+    Linear,
+    /// Catmull-Rom spline through neighboring samples.
+    Cubic,
+    /// Band-limited windowed sinc. Also filters out content that can't be
+    /// represented at the output rate, instead of aliasing it.
+    Sinc,
+}
+
+/// Resampler with a choice of interpolation kernel.
+///
+/// For sinc interpolation, when reading the input faster than the output rate
+/// the kernel is widened so that it also acts as a low-pass filter below the
+/// output Nyquist frequency, so content that can't be represented at the
+/// output rate is attenuated instead of aliased.
+pub struct Resampler {
+    interpolation: Interpolation,
+    /// Input samples per output sample.
+    step: f64,
+    /// Kernel cutoff, relative to the input Nyquist frequency.
+    cutoff: f64,
+    /// Half-width of the kernel, in input samples.
+    half_width: i64,
+    history: VecDeque<[f32; 2]>,
+    /// Input index of `history[0]`.
+    base: i64,
+    /// Input length, once the input has been exhausted.
+    input_len: Option<i64>,
+    /// Position of the next output sample, in input samples.
+    time: f64,
+}
+
+impl Resampler {
+    /// `speed` is the playback speed; 2.0 reads the input twice as fast.
+    pub fn new(
+        input_rate: u32,
+        output_rate: u32,
+        speed: f64,
+        interpolation: Interpolation,
+    ) -> Self {
+        let step = speed * input_rate as f64 / output_rate as f64;
+        // Leave a small transition band below Nyquist.
+        let cutoff = 0.95 * (1.0 / step).min(1.0);
+        let half_width = match interpolation {
+            Interpolation::Linear => 1,
+            Interpolation::Cubic => 2,
+            Interpolation::Sinc => (ZERO_CROSSINGS / cutoff).ceil() as i64,
+        };
+        Self {
+            interpolation,
+            step,
+            cutoff,
+            half_width,
+            history: VecDeque::new(),
+            base: 0,
+            input_len: None,
+            time: 0.0,
+        }
+    }
+
+    /// Weight of the input sample at `offset` from the output position.
+    fn kernel(&self, offset: f64) -> f64 {
+        match self.interpolation {
+            Interpolation::Linear => (1.0 - offset.abs()).max(0.0),
+            Interpolation::Cubic => {
+                let x = offset.abs();
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Interpolation::Sinc => self.sinc_kernel(offset),
+        }
+    }
+
+    fn sinc_kernel(&self, offset: f64) -> f64 {
+        let x = self.cutoff * offset;
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            (PI * x).sin() / (PI * x)
+        };
+        // Blackman window
+        let w = 0.5 + 0.5 * offset / self.half_width as f64;
+        let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+        self.cutoff * sinc * window
+    }
+
+    /// Produce the next output frame, reading from `input` as needed.
+    ///
+    /// Returns `None` once the input is exhausted and all of it has been output.
+    pub fn next(&mut self, input: &mut impl Iterator<Item = [f32; 2]>) -> Option<[f32; 2]> {
+        let center = self.time.floor() as i64;
+        let lo = center - self.half_width + 1;
+        let hi = center + self.half_width;
+
+        while self.base + (self.history.len() as i64) <= hi {
+            if self.input_len.is_none() {
+                if let Some(frame) = input.next() {
+                    self.history.push_back(frame);
+                    continue;
+                }
+                self.input_len = Some(self.base + self.history.len() as i64);
+            }
+            // Pad with silence past the end of the input.
+            self.history.push_back([0.0; 2]);
+        }
+        if let Some(len) = self.input_len {
+            if self.time >= len as f64 {
+                return None;
+            }
+        }
+        while self.base < lo && !self.history.is_empty() {
+            self.history.pop_front();
+            self.base += 1;
+        }
+
+        let mut out = [0.0f64; 2];
+        for n in lo.max(self.base)..=hi {
+            let weight = self.kernel(self.time - n as f64);
+            let frame = self.history[(n - self.base) as usize];
+            out[0] += weight * frame[0] as f64;
+            out[1] += weight * frame[1] as f64;
+        }
+        self.time += self.step;
+        Some(out.map(|x| x as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, rate: u32, len: usize) -> impl Iterator<Item = [f32; 2]> {
+        (0..len).map(move |i| {
+            let x = (2.0 * PI * freq * i as f64 / rate as f64).sin() as f32;
+            [x, x]
+        })
+    }
+
+    /// The left channel of the output, skipping the kernel's start-up
+    /// transient at each end.
+    fn resample(resampler: &mut Resampler, input: impl Iterator<Item = [f32; 2]>) -> Vec<f64> {
+        let mut input = input;
+        let mut out = Vec::new();
+        while let Some([x, _]) = resampler.next(&mut input) {
+            out.push(x as f64);
+        }
+        let margin = 2 * resampler.half_width as usize;
+        out[margin..out.len() - margin].to_vec()
+    }
+
+    fn rms(signal: &[f64]) -> f64 {
+        (signal.iter().map(|x| x * x).sum::<f64>() / signal.len() as f64).sqrt()
+    }
+
+    /// Ratio of everything but a sine at `freq` to the sine, from a least
+    /// squares fit of its amplitude and phase.
+    fn distortion(signal: &[f64], freq: f64, rate: u32) -> f64 {
+        let w = 2.0 * PI * freq / rate as f64;
+        let (mut ss, mut sc, mut cc, mut xs, mut xc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (i, &x) in signal.iter().enumerate() {
+            let (s, c) = (w * i as f64).sin_cos();
+            ss += s * s;
+            sc += s * c;
+            cc += c * c;
+            xs += x * s;
+            xc += x * c;
+        }
+        let det = ss * cc - sc * sc;
+        let a = (xs * cc - xc * sc) / det;
+        let b = (xc * ss - xs * sc) / det;
+        let fit: Vec<f64> = (0..signal.len())
+            .map(|i| {
+                let (s, c) = (w * i as f64).sin_cos();
+                a * s + b * c
+            })
+            .collect();
+        let residual: Vec<f64> = signal.iter().zip(&fit).map(|(x, f)| x - f).collect();
+        rms(&residual) / rms(&fit)
+    }
+
+    #[test]
+    fn downsampling_attenuates_content_above_nyquist() {
+        // 30 kHz can't be represented at 48 kHz; aliased, it would show up
+        // at 18 kHz at full level.
+        let mut resampler = Resampler::new(192000, 48000, 1.0, Interpolation::Sinc);
+        let out = resample(&mut resampler, sine(30000.0, 192000, 192000));
+        assert!(rms(&out) < 0.01, "30 kHz tone at {} RMS", rms(&out));
+
+        let mut resampler = Resampler::new(192000, 48000, 1.0, Interpolation::Sinc);
+        let out = resample(&mut resampler, sine(1000.0, 192000, 192000));
+        let level = rms(&out) * 2f64.sqrt();
+        assert!(
+            (0.99..=1.01).contains(&level),
+            "1 kHz tone at level {level}"
+        );
+    }
+
+    #[test]
+    fn slowed_sine_stays_clean() {
+        const RATE: u32 = 48000;
+        const SPEED: f64 = 0.7;
+        let freq = 3000.0;
+        let mut thd = Vec::new();
+        for interpolation in [
+            Interpolation::Linear,
+            Interpolation::Cubic,
+            Interpolation::Sinc,
+        ] {
+            let mut resampler = Resampler::new(RATE, RATE, SPEED, interpolation);
+            let out = resample(&mut resampler, sine(freq, RATE, RATE as usize));
+            thd.push(distortion(&out, freq * SPEED, RATE));
+        }
+        let [linear, cubic, sinc] = thd[..] else {
+            unreachable!()
+        };
+        assert!(sinc < 1e-4, "sinc distortion {sinc}");
+        assert!(
+            sinc < cubic && cubic < linear,
+            "distortion: linear {linear}, cubic {cubic}, sinc {sinc}"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Remembering the playback position of each file, to resume from later.
+//!
+//! Positions are stored in a tab-separated file in the user's state
+//! directory, one line per file: frame position, file size, modification time
+//! and canonical path. An entry is ignored if the file's size or modification
+//! time has changed since it was written.
+
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::source::{FileSource, SampleSource};
+
+/// Number of files to remember, most recent first.
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileKey {
+    path: PathBuf,
+    size: u64,
+    mtime: u64,
+}
+
+impl FileKey {
+    fn new(path: &Path) -> std::io::Result<Self> {
+        let path = path.canonicalize()?;
+        let metadata = path.metadata()?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Ok(Self {
+            path,
+            size: metadata.len(),
+            mtime,
+        })
+    }
+}
+
+struct Entry {
+    position: u64,
+    key: FileKey,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+        let position = fields.next()?.parse().ok()?;
+        let size = fields.next()?.parse().ok()?;
+        let mtime = fields.next()?.parse().ok()?;
+        let path = PathBuf::from(fields.next()?);
+        Some(Self {
+            position,
+            key: FileKey { path, size, mtime },
+        })
+    }
+
+    fn format(&self) -> Option<String> {
+        // Paths that can't be written unambiguously are not remembered.
+        let path = self
+            .key
+            .path
+            .to_str()
+            .filter(|p| !p.contains(['\t', '\n']))?;
+        Some(format!(
+            "{}\t{}\t{}\t{path}",
+            self.position, self.key.size, self.key.mtime
+        ))
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("glowie").join("positions.tsv"))
+}
+
+fn load_entries() -> Vec<Entry> {
+    let Some(path) = state_path() else {
+        return Vec::new();
+    };
+    std::fs::read_to_string(path)
+        .map(|contents| contents.lines().filter_map(Entry::parse).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct Progress {
+    position: AtomicU64,
+    finished: AtomicBool,
+}
+
+/// The saved position of one file, and the tracking of its new position.
+pub struct Resume {
+    key: FileKey,
+    progress: Arc<Progress>,
+}
+
+impl Resume {
+    pub fn new(path: &Path) -> Option<Self> {
+        let key = match FileKey::new(path) {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("cannot remember position of {}: {e}", path.display());
+                return None;
+            }
+        };
+        Some(Self {
+            key,
+            progress: Default::default(),
+        })
+    }
+
+    /// Skip to the saved position of the file, if there is one.
+    pub fn seek(&self, source: &mut FileSource) {
+        let Some(entry) = load_entries().into_iter().find(|e| e.key == self.key) else {
+            return;
+        };
+        // Beyond the end, if the file was changed in place; this stops at the end.
+        let skipped = source.skip(entry.position);
+        self.progress.position.store(skipped, Ordering::Relaxed);
+        let seconds = skipped / u64::from(source.sample_rate()).max(1);
+        info!(
+            "resuming at {}:{:02} (use --no-resume to start over)",
+            seconds / 60,
+            seconds % 60
+        );
+    }
+
+    /// Wrap a source so that its position is tracked.
+    pub fn track<S: SampleSource>(&self, source: S) -> Tracked<S> {
+        Tracked {
+            inner: source,
+            progress: Arc::clone(&self.progress),
+        }
+    }
+
+    /// Write the current position to the state file. A file that was played to
+    /// the end is forgotten, so that it starts from the beginning next time.
+    pub fn save(&self) {
+        let Some(path) = state_path() else {
+            return;
+        };
+        let mut entries = load_entries();
+        entries.retain(|e| e.key.path != self.key.path);
+        if !self.progress.finished.load(Ordering::Relaxed) {
+            entries.insert(
+                0,
+                Entry {
+                    position: self.progress.position.load(Ordering::Relaxed),
+                    key: self.key.clone(),
+                },
+            );
+        }
+        entries.truncate(MAX_ENTRIES);
+
+        let mut contents = String::new();
+        for line in entries.iter().filter_map(Entry::format) {
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&path, contents));
+        if let Err(e) = result {
+            warn!("failed to save position to {}: {e}", path.display());
+        }
+    }
+}
+
+/// A source that reports how far it has been read.
+pub struct Tracked<S> {
+    inner: S,
+    progress: Arc<Progress>,
+}
+
+impl<S: SampleSource> SampleSource for Tracked<S> {
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        let frame = self.inner.next_frame();
+        if frame.is_some() {
+            self.progress.position.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.progress.finished.store(true, Ordering::Relaxed);
+        }
+        frame
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn seek(&mut self, frame: u64) -> anyhow::Result<u64> {
+        let position = self.inner.seek(frame)?;
+        self.progress.position.store(position, Ordering::Relaxed);
+        self.progress.finished.store(false, Ordering::Relaxed);
+        Ok(position)
+    }
+}
@@ -1,26 +1,75 @@
 use bytemuck::{Pod, Zeroable};
 use glam::Vec2;
+use log::{debug, info};
+use std::time::Instant;
 use wgpu::RenderPipelineDescriptor;
 
-use crate::GraphicsContext;
+use crate::gfx::GraphicsContext;
+use crate::queue::SampleQueue;
 
 const STORAGE_DIMENSION: wgpu::TextureDimension = wgpu::TextureDimension::D2;
 const STORAGE_VIEW_DIMENSION: wgpu::TextureViewDimension = wgpu::TextureViewDimension::D2;
-const STORAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+/// The beam in the first channel, and its afterglow in the second.
+const STORAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
 
-const MAX_LINES: usize = 65536;
+/// Chunk offsets and sizes are packed into 16 bits, so every line must be
+/// addressable with a `u16`.
+const MAX_LINES: usize = u16::MAX as usize;
+/// Most samples drawn in one sub-step, so that line times, which count
+/// samples, stay exact as `f32`.
+const MAX_FRAME_SAMPLES: usize = 1 << 24;
+/// Must match `cs_accumulate` in `scope.wgsl`.
+const COMPUTE_WORKGROUP_SIZE: u32 = 8;
+/// Largest number of chunks along each side of the chunk grid.
+const MAX_GRID_SIZE: usize = 32;
+const MAX_CHUNKS: usize = MAX_GRID_SIZE * MAX_GRID_SIZE;
+/// Preferred width of a chunk, in pixels.
+const TARGET_CHUNK_PIXELS: u32 = 24;
+const MAX_ZOOM: f32 = 64.0;
+const DEFAULT_DECAY: f32 = 1.0 - 5e-4;
+pub const DEFAULT_DECAY_FALLOFF: f32 = 2.0;
+const DEFAULT_MAX_RETAINED: usize = 48000;
+/// Limit on the intensity normalization factor, in either direction.
+const MAX_INTENSITY_SCALE: f32 = 16.0;
+/// Number of lines per frame at which intensity normalization has no effect.
+const NORMAL_LINES_PER_FRAME: f32 = 800.0;
+/// Frame rate at which normalized intensity isn't scaled by the frame time.
+pub const REFERENCE_FRAME_RATE: f32 = 60.0;
 
 #[derive(Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 struct Config {
-    chunks: [Chunk4; 64],
+    chunks: [Chunk4; MAX_CHUNKS / 4],
     window_size: [f32; 2],
     line_radius: f32,
     decay: f32,
     sigma: f32,
     intensity: f32,
     total_time: f32,
-    _pad: [u8; 4],
+    beam_profile: u32,
+    background_opacity: f32,
+    blend_mode: u32,
+    intensity_scale: f32,
+    /// Decay at the corners of the visible square, instead of `decay`.
+    edge_decay: f32,
+    /// Exponent of the blend from `decay` at the center to `edge_decay`.
+    decay_falloff: f32,
+    /// Number of chunks along each side of the chunk grid.
+    grid_size: u32,
+    /// Bitmask of `Mirror` axes, or 0.
+    mirror: u32,
+    afterglow_decay: f32,
+    /// Color of the beam at low intensity; it fades to white at the core.
+    beam_color: [f32; 4],
+    afterglow_color: [f32; 4],
+    /// Fraction of the beam's light that goes into the afterglow.
+    afterglow_level: f32,
+    /// Amplitude of the output dither, in encoded color values, or 0.
+    dither_step: f32,
+    /// Number of frames drawn so far, for varying the dither over time.
+    frame_index: u32,
+    /// Whether the target encodes to sRGB, so the dither is applied in sRGB.
+    dither_srgb: u32,
 }
 
 impl Default for Config {
@@ -28,12 +77,249 @@ impl Default for Config {
         Self {
             window_size: [360.0, 360.0],
             line_radius: 5.0,
-            decay: 1.0 - 5e-4,
+            decay: DEFAULT_DECAY,
             sigma: 5e-3,
             intensity: 2e-5,
             total_time: 0.0,
+            beam_profile: BeamProfile::Gaussian as u32,
+            background_opacity: 0.0,
+            blend_mode: BlendMode::Replace as u32,
+            intensity_scale: 1.0,
+            edge_decay: DEFAULT_DECAY,
+            decay_falloff: DEFAULT_DECAY_FALLOFF,
+            grid_size: 16,
+            mirror: 0,
+            afterglow_decay: 1.0,
+            beam_color: [0.0, 1.0, 0.0, 1.0],
+            afterglow_color: [0.0; 4],
+            afterglow_level: 0.0,
+            dither_step: 0.0,
+            frame_index: 0,
+            dither_srgb: 0,
             chunks: std::array::from_fn(|_| Chunk4::default()),
-            _pad: [0; 4],
+        }
+    }
+}
+
+impl Config {
+    /// Force every parameter into a range the shader can handle, replacing
+    /// non-finite values with their defaults.
+    ///
+    /// | field         | range            |
+    /// |---------------|------------------|
+    /// | `window_size` | 1 - 16384        |
+    /// | `line_radius` | 0 - 100          |
+    /// | `decay`       | 0 - 1            |
+    /// | `sigma`       | 1e-5 - 1         |
+    /// | `intensity`   | 0 - 1            |
+    /// | `total_time`  | 0 - `MAX_FRAME_SAMPLES` |
+    /// | `background_opacity` | 0 - 1     |
+    /// | `intensity_scale` | 0 - 16       |
+    /// | `edge_decay`  | 0 - 1            |
+    /// | `decay_falloff` | 0.1 - 10       |
+    /// | `grid_size`   | power of 2, up to `MAX_GRID_SIZE` |
+    /// | `beam_color`  | 0 - 1            |
+    /// | `afterglow_color` | 0 - 1        |
+    /// | `afterglow_decay` | 0 - 1        |
+    /// | `afterglow_level` | 0 - 1        |
+    /// | `dither_step` | 0 - 1            |
+    fn validate_and_clamp(&mut self) {
+        let default = Self::default();
+        for (value, default) in self.window_size.iter_mut().zip(default.window_size) {
+            *value = sanitize(*value, default, 1.0, 16384.0);
+        }
+        self.line_radius = sanitize(self.line_radius, default.line_radius, 0.0, 100.0);
+        self.decay = sanitize(self.decay, default.decay, 0.0, 1.0);
+        self.edge_decay = sanitize(self.edge_decay, self.decay, 0.0, 1.0);
+        self.decay_falloff = sanitize(self.decay_falloff, default.decay_falloff, 0.1, 10.0);
+        self.sigma = sanitize(self.sigma, default.sigma, 1e-5, 1.0);
+        self.intensity = sanitize(self.intensity, default.intensity, 0.0, 1.0);
+        self.total_time = sanitize(
+            self.total_time,
+            default.total_time,
+            0.0,
+            MAX_FRAME_SAMPLES as f32,
+        );
+        self.background_opacity = sanitize(
+            self.background_opacity,
+            default.background_opacity,
+            0.0,
+            1.0,
+        );
+        self.intensity_scale = sanitize(
+            self.intensity_scale,
+            default.intensity_scale,
+            0.0,
+            MAX_INTENSITY_SCALE,
+        );
+        for (value, default) in self.beam_color.iter_mut().zip(default.beam_color) {
+            *value = sanitize(*value, default, 0.0, 1.0);
+        }
+        for (value, default) in self.afterglow_color.iter_mut().zip(default.afterglow_color) {
+            *value = sanitize(*value, default, 0.0, 1.0);
+        }
+        self.afterglow_decay = sanitize(self.afterglow_decay, default.afterglow_decay, 0.0, 1.0);
+        self.afterglow_level = sanitize(self.afterglow_level, default.afterglow_level, 0.0, 1.0);
+        self.dither_step = sanitize(self.dither_step, default.dither_step, 0.0, 1.0);
+        self.dither_srgb = self.dither_srgb.min(1);
+        if !self.grid_size.is_power_of_two() || self.grid_size as usize > MAX_GRID_SIZE {
+            self.grid_size = default.grid_size;
+        }
+        if self.beam_profile > BeamProfile::Hard as u32 {
+            self.beam_profile = default.beam_profile;
+        }
+        if self.blend_mode > BlendMode::Alpha as u32 {
+            self.blend_mode = default.blend_mode;
+        }
+        self.mirror &= Mirror::Quad as u32;
+    }
+}
+
+fn sanitize(value: f32, default: f32, min: f32, max: f32) -> f32 {
+    if value.is_finite() {
+        value.clamp(min, max)
+    } else {
+        default
+    }
+}
+
+/// Cross-section of the beam intensity as a function of distance.
+///
+/// Must match the constants in `scope.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[repr(u32)]
+pub enum BeamProfile {
+    /// Soft CRT-like glow.
+    Gaussian = 0,
+    /// Sharp core with a long tail.
+    Exponential = 1,
+    /// Uniform intensity within the beam width, for a crisp vector look.
+    Hard = 2,
+}
+
+/// Axes across which the image is reflected.
+///
+/// Must match the constants in `scope.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[repr(u32)]
+pub enum Mirror {
+    /// Reflect the right half onto the left.
+    X = 1,
+    /// Reflect the top half onto the bottom.
+    Y = 2,
+    /// Reflect the top-right quadrant onto the others.
+    Quad = 3,
+}
+
+/// Second persistence layer; see [`Scope::set_afterglow`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Afterglow {
+    /// Linear RGB.
+    pub color: [f32; 3],
+    /// Fraction of intensity retained after each sample.
+    pub decay: f32,
+    /// Fraction of the beam's light that goes into the afterglow.
+    pub level: f32,
+}
+
+/// Polar mapping of samples; see [`Scope::set_polar`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Polar {
+    /// Radius of the circle that the first channel's minimum maps to, where
+    /// its maximum maps to the edge of the screen.
+    pub inner_radius: f32,
+    /// Number of turns around the circle across the second channel's range.
+    pub turns: f32,
+}
+
+impl Polar {
+    fn map(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let radius = self.inner_radius + (1.0 - self.inner_radius) * 0.5 * (x + 1.0);
+        let (sin, cos) = (std::f32::consts::PI * self.turns * y).sin_cos();
+        [radius * cos, radius * sin]
+    }
+}
+
+/// Emulation of a CRT phosphor, as a beam color and optional afterglow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Phosphor {
+    /// Green, medium persistence; the classic oscilloscope look.
+    P1,
+    /// Blue-white flash over a long yellow afterglow, as on radar displays.
+    P7,
+    /// Bright green, short persistence.
+    P31,
+    /// The beam color and decay as configured, with the afterglow given by
+    /// `--afterglow-*`.
+    Custom,
+}
+
+impl Phosphor {
+    pub fn beam_color(self) -> Option<[f32; 3]> {
+        match self {
+            Self::P1 => Some([0.1, 1.0, 0.1]),
+            Self::P7 => Some([0.55, 0.65, 1.0]),
+            Self::P31 => Some([0.3, 1.0, 0.2]),
+            Self::Custom => None,
+        }
+    }
+
+    pub fn decay(self) -> Option<f32> {
+        match self {
+            Self::P1 => Some(1.0 - 3e-4),
+            Self::P7 => Some(1.0 - 2e-3),
+            Self::P31 => Some(1.0 - 1e-3),
+            Self::Custom => None,
+        }
+    }
+
+    pub fn afterglow(self) -> Option<Afterglow> {
+        match self {
+            Self::P7 => Some(Afterglow {
+                color: [1.0, 0.6, 0.05],
+                decay: 1.0 - 2e-5,
+                level: 0.05,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// How the scope image is combined with the render target.
+///
+/// Must match the constants in `scope.wgsl`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[cfg_attr(
+    feature = "http-control",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+#[repr(u32)]
+pub enum BlendMode {
+    /// Overwrite the target.
+    #[default]
+    Replace = 0,
+    /// Add the beam's light to the target.
+    Additive = 1,
+    /// Draw over the target, with the beam's brightness as its opacity.
+    Alpha = 2,
+}
+
+impl BlendMode {
+    const ALL: [Self; 3] = [Self::Replace, Self::Additive, Self::Alpha];
+
+    fn blend_state(self) -> wgpu::BlendState {
+        match self {
+            Self::Replace => wgpu::BlendState::REPLACE,
+            Self::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+            Self::Alpha => wgpu::BlendState::ALPHA_BLENDING,
         }
     }
 }
@@ -58,7 +344,7 @@ impl Default for Chunk4 {
 struct Line {
     // 2x16snorm
     start: u32,
-    // 2x16snorm
+    // 2x16snorm, halved so that segments across the whole screen fit
     v: u32,
     time: f32,
 }
@@ -85,8 +371,98 @@ impl Default for Line {
     }
 }
 
+/// The region of sample space that is visible on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub zoom: f32,
+    /// Sample coordinate shown at the center of the screen.
+    pub center: Vec2,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            center: Vec2::ZERO,
+        }
+    }
+}
+
+impl View {
+    /// Keep the zoom in range, and the visible area within the signal bounds.
+    fn clamp(&mut self) {
+        self.zoom = self.zoom.clamp(1.0, MAX_ZOOM);
+        let limit = Vec2::splat(1.0 - 1.0 / self.zoom);
+        self.center = self.center.clamp(-limit, limit);
+    }
+
+    /// Transform a segment into screen space, clipping it to the visible area.
+    fn project(&self, start: [f32; 2], end: [f32; 2]) -> Option<(Vec2, Vec2)> {
+        let start = (Vec2::from(start) - self.center) * self.zoom;
+        let end = (Vec2::from(end) - self.center) * self.zoom;
+        if self.zoom > 1.0 {
+            clip_segment(start, end)
+        } else {
+            Some((start, end))
+        }
+    }
+}
+
+/// Liang-Barsky clipping of a segment to the square [-1, 1].
+fn clip_segment(start: Vec2, end: Vec2) -> Option<(Vec2, Vec2)> {
+    let d = end - start;
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+    for (p, q) in [
+        (-d.x, start.x + 1.0),
+        (d.x, 1.0 - start.x),
+        (-d.y, start.y + 1.0),
+        (d.y, 1.0 - start.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = t0.max(r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = t1.min(r);
+            }
+        }
+    }
+    Some((start + d * t0, start + d * t1))
+}
+
+/// Counters describing the scope's recent workload.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Metrics {
+    /// Line segments uploaded for the last frame.
+    pub lines: usize,
+    /// Time covered by the last frame, in samples.
+    pub total_time: f32,
+    /// Samples received through `extend`.
+    pub pushed: u64,
+    /// Samples discarded before they could be drawn.
+    pub dropped: u64,
+    /// Overruns reported by the sample producer.
+    pub overruns: u64,
+    /// Frames the sample producer lost in those overruns.
+    pub overrun_frames: u64,
+    /// Lines in the fullest chunk, in any sub-step of the last frame.
+    pub busiest_chunk: usize,
+}
+
 #[allow(dead_code)]
 struct SizeDependent {
+    /// Read by `front`, so after a draw, this holds its result.
     a: wgpu::Texture,
     b: wgpu::Texture,
     a_view: wgpu::TextureView,
@@ -96,20 +472,27 @@ struct SizeDependent {
 }
 
 impl SizeDependent {
-    fn new(gfx: &GraphicsContext, texture_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
-        let window_size = gfx.window.inner_size();
+    fn new(
+        gfx: &GraphicsContext,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        size: [u32; 2],
+    ) -> Self {
         let texture_descriptor = wgpu::TextureDescriptor {
             label: Some("Scope.texture_descriptor"),
             size: wgpu::Extent3d {
-                width: window_size.width,
-                height: window_size.height,
+                width: size[0],
+                height: size[1],
                 ..Default::default()
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: STORAGE_DIMENSION,
             format: STORAGE_FORMAT,
-            usage: wgpu::TextureUsages::STORAGE_BINDING,
+            // Read as a sampled texture, because read-only storage access to
+            // Rg32Float needs an adapter-specific feature.
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         };
 
@@ -160,46 +543,124 @@ impl SizeDependent {
             back,
         }
     }
+
+    /// Reverse the direction of rendering, after a draw.
+    fn swap(&mut self) {
+        std::mem::swap(&mut self.front, &mut self.back);
+        std::mem::swap(&mut self.a, &mut self.b);
+    }
+}
+
+/// Uniforms and lines for one persistence pass. Each sub-step of a frame needs
+/// its own, because all buffer writes land before any of the frame's passes run.
+struct PassBuffers {
+    config: wgpu::Buffer,
+    lines: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl PassBuffers {
+    fn new(gfx: &GraphicsContext, uniform_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let config = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scope.config_buffer"),
+            size: std::mem::size_of::<Config>().try_into().unwrap(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+        let lines = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scope.line_buffer"),
+            size: (MAX_LINES * std::mem::size_of::<Line>())
+                .try_into()
+                .unwrap(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scope.config_bind_group"),
+            layout: uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: config.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lines.as_entire_binding(),
+                },
+            ],
+        });
+        Self {
+            config,
+            lines,
+            bind_group,
+        }
+    }
 }
 
 pub struct Scope {
     gfx: GraphicsContext,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
     config: Config,
-    config_buffer: wgpu::Buffer,
     chunk_lines: Vec<Vec<Line>>,
     lines: Vec<Line>,
     samples: Vec<[f32; 2]>,
-    line_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    /// One per sub-step of a frame.
+    passes: Vec<PassBuffers>,
+    /// Target for the color output of all but the last sub-step.
+    scratch: Option<wgpu::TextureView>,
     texture_bind_group_layout: wgpu::BindGroupLayout,
+    background_bind_group_layout: wgpu::BindGroupLayout,
+    background_sampler: wgpu::Sampler,
+    background_bind_group: wgpu::BindGroup,
     size_dependent: SizeDependent,
-    pipeline: wgpu::RenderPipeline,
+    pipelines: [wgpu::RenderPipeline; 3],
+    compute_pipeline: wgpu::ComputePipeline,
+    /// Show the result of `compute_pipeline`, one per blend mode.
+    display_pipelines: [wgpu::RenderPipeline; 3],
+    occupancy_pipeline: wgpu::RenderPipeline,
+    compute_persistence: bool,
+    blend_mode: BlendMode,
     sample_count: usize,
     frame_count: usize,
+    /// `sample_count` and `frame_count` when pacing was last reset.
+    pacing_start: [usize; 2],
+    metrics: Metrics,
+    view: View,
+    frozen: bool,
+    max_retained: usize,
+    paced: bool,
+    normalize_intensity: bool,
+    /// Fixed time between frames, in seconds, instead of the measured one.
+    frame_interval: Option<f32>,
+    last_frame: Option<Instant>,
+    beam_scales_with_zoom: bool,
+    source: Option<SampleQueue>,
+    polar: Option<Polar>,
+    alignment: Option<usize>,
+    /// Rotation of the image, in radians per sample.
+    spin: f64,
+    /// The samples drawn in the last frame.
+    last_drawn: Vec<[f32; 2]>,
+    /// While held, the samples that are drawn every frame instead of new ones.
+    held: Option<Vec<[f32; 2]>>,
 }
 
 impl Scope {
-    pub fn new(gfx: GraphicsContext) -> Self {
-        let config = Config::default();
-        let config_buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Scope.config_buffer"),
-            size: std::mem::size_of::<Config>().try_into().unwrap(),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-            mapped_at_creation: false,
-        });
-
+    /// Create a scope that renders to targets of the given format and size.
+    pub fn new(gfx: GraphicsContext, format: wgpu::TextureFormat, size: [u32; 2]) -> Self {
+        let size = size.map(|x| x.max(1));
+        let grid_size = grid_size_for(size);
+        info!("using a {grid_size}x{grid_size} chunk grid");
+        let config = Config {
+            window_size: size.map(|x| x as f32),
+            grid_size,
+            ..Default::default()
+        };
         let lines = vec![];
         let samples = vec![[0.0; 2]];
-        let chunk_lines = vec![Vec::new(); 256];
-
-        let line_buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Scope.line_buffer"),
-            size: (MAX_LINES * std::mem::size_of::<Line>())
-                .try_into()
-                .unwrap(),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
+        let chunk_lines = vec![Vec::new(); MAX_CHUNKS];
 
         let uniform_bind_group_layout =
             gfx.device
@@ -208,7 +669,7 @@ impl Scope {
                     entries: &[
                         wgpu::BindGroupLayoutEntry {
                             binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::Buffer {
                                 ty: wgpu::BufferBindingType::Uniform,
                                 has_dynamic_offset: false,
@@ -218,7 +679,7 @@ impl Scope {
                         },
                         wgpu::BindGroupLayoutEntry {
                             binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::Buffer {
                                 ty: wgpu::BufferBindingType::Storage { read_only: true },
                                 has_dynamic_offset: false,
@@ -229,20 +690,7 @@ impl Scope {
                     ],
                 });
 
-        let uniform_bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Scope.config_bind_group"),
-            layout: &uniform_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: config_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: line_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let passes = vec![PassBuffers::new(&gfx, &uniform_bind_group_layout)];
 
         let texture_bind_group_layout =
             gfx.device
@@ -251,17 +699,17 @@ impl Scope {
                     entries: &[
                         wgpu::BindGroupLayoutEntry {
                             binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadOnly,
-                                format: STORAGE_FORMAT,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
                                 view_dimension: STORAGE_VIEW_DIMENSION,
+                                multisampled: false,
                             },
                             count: None,
                         },
                         wgpu::BindGroupLayoutEntry {
                             binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::StorageTexture {
                                 access: wgpu::StorageTextureAccess::WriteOnly,
                                 format: STORAGE_FORMAT,
@@ -272,7 +720,43 @@ impl Scope {
                     ],
                 });
 
-        let size_dependent = SizeDependent::new(&gfx, &texture_bind_group_layout);
+        let size_dependent = SizeDependent::new(&gfx, &texture_bind_group_layout, size);
+
+        let background_bind_group_layout =
+            gfx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Scope.background_bind_group_layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let background_sampler = gfx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Scope.background_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let background_bind_group = create_background_bind_group(
+            &gfx,
+            &background_bind_group_layout,
+            &background_sampler,
+            &image::RgbaImage::new(1, 1),
+        );
 
         let shader_module = gfx
             .device
@@ -282,15 +766,76 @@ impl Scope {
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Scope.pipeline_layout"),
-                bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &texture_bind_group_layout,
+                    &background_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let pipeline = gfx
+        // Blend state is baked into the pipeline, so create one per mode.
+        let create_pipelines = |label, entry_point| {
+            BlendMode::ALL.map(|mode| {
+                gfx.device
+                    .create_render_pipeline(&RenderPipelineDescriptor {
+                        label: Some(label),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader_module,
+                            entry_point: "vs_main",
+                            buffers: &[],
+                        },
+                        primitive: wgpu::PrimitiveState {
+                            topology: wgpu::PrimitiveTopology::TriangleStrip,
+                            ..Default::default()
+                        },
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader_module,
+                            entry_point,
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format,
+                                blend: Some(mode.blend_state()),
+                                write_mask: wgpu::ColorWrites::default(),
+                            })],
+                        }),
+                        multiview: None,
+                    })
+            })
+        };
+        let pipelines = create_pipelines("Scope.pipeline", "fs_main");
+        let display_pipelines = create_pipelines("Scope.display_pipeline", "fs_display");
+
+        let compute_pipeline_layout =
+            gfx.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Scope.compute_pipeline_layout"),
+                    bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let compute_pipeline =
+            gfx.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("Scope.compute_pipeline"),
+                    layout: Some(&compute_pipeline_layout),
+                    module: &shader_module,
+                    entry_point: "cs_accumulate",
+                });
+
+        let occupancy_pipeline_layout =
+            gfx.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Scope.occupancy_pipeline_layout"),
+                    bind_group_layouts: &[&uniform_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let occupancy_pipeline = gfx
             .device
             .create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("Scope.pipeline"),
-                layout: Some(&pipeline_layout),
+                label: Some("Scope.occupancy_pipeline"),
+                layout: Some(&occupancy_pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &shader_module,
                     entry_point: "vs_main",
@@ -304,10 +849,10 @@ impl Scope {
                 multisample: wgpu::MultisampleState::default(),
                 fragment: Some(wgpu::FragmentState {
                     module: &shader_module,
-                    entry_point: "fs_main",
+                    entry_point: "fs_occupancy",
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: gfx.surface_format,
-                        blend: Some(wgpu::BlendState::REPLACE),
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::default(),
                     })],
                 }),
@@ -316,53 +861,441 @@ impl Scope {
 
         Self {
             gfx: gfx.clone(),
+            format,
+            size,
             config,
-            config_buffer,
             lines,
             chunk_lines,
             samples,
-            line_buffer,
-            uniform_bind_group,
+            uniform_bind_group_layout,
+            passes,
+            scratch: None,
             texture_bind_group_layout,
+            background_bind_group_layout,
+            background_sampler,
+            background_bind_group,
             size_dependent,
-            pipeline,
+            pipelines,
+            compute_pipeline,
+            display_pipelines,
+            occupancy_pipeline,
+            compute_persistence: false,
+            blend_mode: BlendMode::Replace,
             sample_count: 0,
             frame_count: 0,
+            pacing_start: [0, 0],
+            metrics: Metrics::default(),
+            view: View::default(),
+            frozen: false,
+            max_retained: DEFAULT_MAX_RETAINED,
+            paced: true,
+            normalize_intensity: false,
+            frame_interval: None,
+            last_frame: None,
+            beam_scales_with_zoom: true,
+            source: None,
+            polar: None,
+            alignment: None,
+            spin: 0.0,
+            last_drawn: Vec::new(),
+            held: None,
         }
     }
 
-    pub fn extend(&mut self, frames: impl IntoIterator<Item = [f32; 2]>) {
-        self.samples.extend(frames);
+    /// Add a sequence of points to the beam path.
+    ///
+    /// Anything that converts to `[f32; 2]` can be used, such as `glam::Vec2`
+    /// or `(f32, f32)`, so the input doesn't have to be stereo audio.
+    /// Coordinates are in the range [-1, 1].
+    pub fn extend<T: Into<[f32; 2]>>(&mut self, frames: impl IntoIterator<Item = T>) {
+        let len = self.samples.len();
+        self.samples.extend(frames.into_iter().map(Into::into));
+        self.metrics.pushed += (self.samples.len() - len) as u64;
+        self.enforce_retention();
+    }
+
+    /// Limit the number of samples waiting to be drawn. If rendering falls
+    /// behind by more than this, the oldest samples are dropped.
+    pub fn set_max_retained(&mut self, max_retained: usize) {
+        self.max_retained = max_retained.max(1);
+        self.enforce_retention();
+    }
+
+    fn enforce_retention(&mut self) {
+        if self.held.is_some() {
+            // Only the latest sample is kept, to resume from when released.
+            let stale = self.samples.len().saturating_sub(1);
+            self.samples.drain(..stale);
+        }
+        if self.samples.len() > self.max_retained {
+            let excess = self.samples.len() - self.max_retained;
+            self.samples.drain(..excess);
+            self.metrics.dropped += excess as u64;
+            debug!("scope fell behind, dropped {excess} samples");
+        }
     }
 
-    fn generate_chunks(&mut self) {
-        let mut sample_limit = self.samples.len();
-        if self.frame_count > 30 {
+    /// Record that the sample producer had to drop data before it reached the scope.
+    pub fn record_overrun(&mut self, dropped: usize) {
+        self.metrics.overruns += 1;
+        self.metrics.overrun_frames += dropped as u64;
+    }
+
+    /// Set the beam falloff profile and its width (in screen units).
+    pub fn set_beam(&mut self, profile: BeamProfile, width: f32) {
+        self.config.beam_profile = profile as u32;
+        self.config.sigma = width;
+    }
+
+    /// Reflect the image for a symmetric, kaleidoscopic look. This only
+    /// changes how the trail is displayed, not the signal.
+    pub fn set_mirror(&mut self, mirror: Option<Mirror>) {
+        self.config.mirror = mirror.map_or(0, |mirror| mirror as u32);
+    }
+
+    /// Add a slower, dimmer second layer under the beam, like the long
+    /// afterglow of some CRT phosphors. `None` removes it.
+    pub fn set_afterglow(&mut self, afterglow: Option<Afterglow>) {
+        let afterglow = afterglow.unwrap_or(Afterglow {
+            color: [0.0; 3],
+            decay: 1.0,
+            level: 0.0,
+        });
+        let [r, g, b] = afterglow.color;
+        self.config.afterglow_color = [r, g, b, 1.0];
+        self.config.afterglow_decay = afterglow.decay;
+        self.config.afterglow_level = afterglow.level;
+    }
+
+    /// Plot the first channel of each sample as a radius, and the second as
+    /// an angle, instead of as X and Y. `None` goes back to X and Y.
+    pub fn set_polar(&mut self, polar: Option<Polar>) {
+        self.polar = polar;
+    }
+
+    /// Rotate the image continuously, by `radians_per_sample` for every sample
+    /// drawn. Positive rates turn counterclockwise.
+    pub fn set_spin(&mut self, radians_per_sample: f64) {
+        self.spin = radians_per_sample;
+    }
+
+    /// Where the sample at `index` is plotted, before the view is applied.
+    fn plot(&self, index: usize) -> [f32; 2] {
+        let sample = self.samples[index];
+        let [x, y] = match &self.polar {
+            Some(polar) => polar.map(sample),
+            None => sample,
+        };
+        if self.spin == 0.0 {
+            return [x, y];
+        }
+        // Timed by the samples drawn so far, so the rotation is as smooth as
+        // the signal, whatever the frame rate.
+        let angle = ((self.sample_count + index) as f64 * self.spin) % std::f64::consts::TAU;
+        let (sin, cos) = (angle as f32).sin_cos();
+        [x * cos - y * sin, x * sin + y * cos]
+    }
+
+    /// Draw a whole number of `period`s, in samples, each frame, so that a
+    /// periodic signal starts every frame at the same phase. `None` draws
+    /// freely.
+    pub fn set_alignment(&mut self, period: Option<usize>) {
+        self.alignment = period.filter(|&period| period > 0);
+    }
+
+    /// Add noise of `amount` output steps to the final colors, which breaks up
+    /// the banding of 8-bit targets in dark gradients. 0 turns it off.
+    pub fn set_dither(&mut self, amount: f32) {
+        self.config.dither_step = amount * quantization_step(self.format);
+        self.config.dither_srgb = self.format.is_srgb() as u32;
+    }
+
+    /// Set the linear RGB color of the beam.
+    pub fn set_beam_color(&mut self, color: [f32; 3]) {
+        self.config.beam_color = [color[0], color[1], color[2], 1.0];
+    }
+
+    /// Linear RGB.
+    #[cfg(feature = "http-control")]
+    pub fn beam_color(&self) -> [f32; 3] {
+        let [r, g, b, _] = self.config.beam_color;
+        [r, g, b]
+    }
+
+    /// Set the energy the beam deposits per sample.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.config.intensity = intensity;
+    }
+
+    #[cfg(feature = "http-control")]
+    pub fn intensity(&self) -> f32 {
+        self.config.intensity
+    }
+
+    /// Draw an image behind the beam, stretched over the scope area.
+    pub fn set_background(&mut self, image: &image::RgbaImage, opacity: f32) {
+        self.background_bind_group = create_background_bind_group(
+            &self.gfx,
+            &self.background_bind_group_layout,
+            &self.background_sampler,
+            image,
+        );
+        self.config.background_opacity = opacity;
+    }
+
+    /// Set how the scope image is combined with the existing contents of the
+    /// render target.
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+        self.config.blend_mode = mode as u32;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.config.decay
+    }
+
+    /// Set the fraction of intensity retained after each sample.
+    pub fn set_decay(&mut self, decay: f32) {
+        self.config.decay = decay;
+        self.config.edge_decay = decay;
+    }
+
+    /// Vary the decay across the screen, from `center` in the middle to `edge`
+    /// at the corners. Larger values of `falloff` keep the center decay over a
+    /// wider area.
+    pub fn set_spatial_decay(&mut self, center: f32, edge: f32, falloff: f32) {
+        self.config.decay = center;
+        self.config.edge_decay = edge;
+        self.config.decay_falloff = falloff;
+    }
+
+    /// Drain samples from `source` at the start of each draw, as if they were
+    /// passed to [`Scope::extend`].
+    pub fn set_source(&mut self, source: Option<SampleQueue>) {
+        self.source = source;
+    }
+
+    /// Take the samples waiting in the source now, instead of at the next draw.
+    pub fn drain_source(&mut self) {
+        if let Some(source) = self.source.take() {
+            source.drain(|frames| self.extend(frames.iter().copied()));
+            self.source = Some(source);
+        }
+    }
+
+    /// When paced (the default), each frame draws roughly the average number
+    /// of samples per frame, to keep up with a real-time source without
+    /// bursts. When not paced, each frame draws as many samples as possible,
+    /// which is what offline rendering wants.
+    pub fn set_paced(&mut self, paced: bool) {
+        self.paced = paced;
+    }
+
+    /// Forget the average number of samples per frame that pacing follows,
+    /// for when the frame rate changes, such as when the window is throttled.
+    pub fn reset_pacing(&mut self) {
+        self.pacing_start = [self.sample_count, self.frame_count];
+    }
+
+    /// When enabled, the intensity of each line is scaled by the number of
+    /// lines drawn in the frame, so that the overall brightness doesn't depend
+    /// on how dense the signal is. Each frame is also scaled by how long it
+    /// stays on screen, so that it doesn't depend on the refresh rate either.
+    pub fn set_normalize_intensity(&mut self, normalize: bool) {
+        self.normalize_intensity = normalize;
+    }
+
+    /// Use a fixed time between frames, in seconds, for intensity
+    /// normalization instead of measuring it. Offline rendering wants this,
+    /// since its frames aren't drawn in real time.
+    pub fn set_frame_interval(&mut self, interval: Option<f32>) {
+        self.frame_interval = interval;
+    }
+
+    /// Number of samples that have been pushed but not yet drawn.
+    pub fn pending(&self) -> usize {
+        // The last sample is retained as the start of the next segment.
+        self.samples.len().saturating_sub(1)
+    }
+
+    /// While frozen, the trail does not decay, and keeps accumulating new samples.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Size of the render target, as of the last resize.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// While held, the samples of the last frame are drawn again every frame,
+    /// and new samples are discarded, so that a single stretch of the signal
+    /// can be studied while the audio keeps playing. Released, drawing resumes
+    /// from the newest samples.
+    pub fn set_held(&mut self, held: bool) {
+        if held == self.held() {
+            return;
+        }
+        if held {
+            self.held = Some(self.last_drawn.clone());
+            self.enforce_retention();
+        } else {
+            self.held = None;
+        }
+    }
+
+    pub fn held(&self) -> bool {
+        self.held.is_some()
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    /// When enabled (the default), the beam widens as the view is zoomed in,
+    /// so it keeps the same thickness relative to the signal. Otherwise it
+    /// keeps the same thickness on screen.
+    pub fn set_beam_scales_with_zoom(&mut self, scales: bool) {
+        self.beam_scales_with_zoom = scales;
+    }
+
+    /// Factor applied to the beam width on screen, accounting for zoom.
+    fn beam_scale(&self) -> f32 {
+        if self.beam_scales_with_zoom {
+            self.view.zoom
+        } else {
+            1.0
+        }
+    }
+
+    /// Set the zoom factor, keeping the same center.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        if zoom.is_finite() {
+            self.view.zoom = zoom;
+            self.view.clamp();
+        }
+    }
+
+    /// Zoom in (or out, if less than 1) around the center of the screen.
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.view.zoom *= factor;
+        self.view.clamp();
+    }
+
+    /// Move the view by a displacement in screen units, where the visible
+    /// square is 2 units wide. Has no effect unless zoomed in.
+    pub fn pan(&mut self, delta: Vec2) {
+        self.view.center -= delta / self.view.zoom;
+        self.view.clamp();
+    }
+
+    /// Move the view so that `center`, in sample coordinates, is at the
+    /// center of the screen. Has no effect unless zoomed in.
+    pub fn set_center(&mut self, center: Vec2) {
+        if center.is_finite() {
+            self.view.center = center;
+            self.view.clamp();
+        }
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Like [`Scope::metrics`], but resets the cumulative counters.
+    pub fn take_metrics(&mut self) -> Metrics {
+        let metrics = self.metrics;
+        self.metrics.pushed = 0;
+        self.metrics.dropped = 0;
+        self.metrics.overruns = 0;
+        self.metrics.overrun_frames = 0;
+        metrics
+    }
+
+    /// Number of samples to draw in the next frame.
+    fn sample_limit(&self) -> usize {
+        let [start_samples, start_frames] = self.pacing_start;
+        let frames = self.frame_count - start_frames;
+        let limit = if self.paced && frames > 30 {
             // Try to render close to real-time, if possible.
-            let samples_per_frame_approx = self.sample_count as f32 / self.frame_count as f32;
-            sample_limit = (1.1 * samples_per_frame_approx) as usize;
+            let samples_per_frame_approx =
+                (self.sample_count - start_samples) as f32 / frames as f32;
+            (1.1 * samples_per_frame_approx) as usize
+        } else {
+            self.samples.len()
+        };
+        match self.alignment {
+            Some(period) => {
+                let periods = ((limit + period / 2) / period)
+                    .max(1)
+                    .min(self.pending() / period);
+                periods * period
+            }
+            None => limit,
         }
+    }
+
+    /// Generate lines for up to `sample_limit` samples.
+    fn generate_chunks(&mut self, sample_limit: usize) {
+        let grid_size = self.config.grid_size as usize;
+        let num_chunks = grid_size * grid_size;
+        let chunk_width = 2.0 / grid_size as f32;
+        // A line is drawn in a chunk if any part of the chunk is within the
+        // visible extent of the beam, about 3 sigma.
+        let cull_distance = (chunk_width * std::f32::consts::FRAC_1_SQRT_2
+            + 3.0 * self.config.sigma * self.beam_scale())
+        .max(chunk_width);
+
         // generate lines from samples, and assign lines to chunks.
-        let mut batch_size = 0;
+        //
+        // When the line buffer fills up, offline rendering leaves the rest of
+        // the samples for the next frame. Real-time rendering can't afford to
+        // fall behind, so it walks the segments from the newest, and drops
+        // the oldest ones that don't fit instead.
+        let segments = self
+            .samples
+            .len()
+            .saturating_sub(1)
+            .min(sample_limit)
+            .min(MAX_FRAME_SAMPLES);
+        let newest_first = self.paced;
+        let mut batch_size = segments;
+        let mut visible_lines = 0;
         let mut line_buffer_size = 0;
-        for seg in self.samples.windows(2).take(sample_limit) {
+        for n in 0..segments {
             // TODO: more efficient chunk iteration
+            let i = if newest_first { segments - 1 - n } else { n };
 
-            let start = Vec2::from(seg[0]);
-            let end = Vec2::from(seg[1]);
+            // Mapped before projecting, so that culling sees the final geometry.
+            let Some((start, end)) = self.view.project(self.plot(i), self.plot(i + 1)) else {
+                // Entirely off-screen.
+                continue;
+            };
 
             let line_data = Line {
                 start: pack2x16snorm(start.into()),
-                v: pack2x16snorm((end - start).into()),
-                time: batch_size as f32,
+                // Halved, because segments can be up to 2 units long.
+                v: pack2x16snorm((0.5 * (end - start)).into()),
+                time: i as f32,
             };
+            visible_lines += 1;
 
-            for chunk_y in 0..16 {
-                for chunk_x in 0..16 {
-                    let i_chunk = 16 * chunk_y + chunk_x;
+            for chunk_y in 0..grid_size {
+                for chunk_x in 0..grid_size {
+                    let i_chunk = grid_size * chunk_y + chunk_x;
 
-                    let chunk_center =
-                        Vec2::new((chunk_x as f32 - 7.5) / 8.0, (chunk_y as f32 - 7.5) / 8.0);
+                    let chunk_center = Vec2::new(
+                        (chunk_x as f32 + 0.5) * chunk_width - 1.0,
+                        (chunk_y as f32 + 0.5) * chunk_width - 1.0,
+                    );
 
                     let u = chunk_center - start;
                     let v = end - start;
@@ -374,26 +1307,41 @@ impl Scope {
                         disp -= proj;
                     }
 
-                    // TODO vary threshold based on config.sigma
-                    if 8.0 * disp.length() < 1.0 {
+                    if disp.length() < cull_distance {
                         self.chunk_lines[i_chunk].push(line_data);
                         line_buffer_size += 1;
                     }
                 }
             }
-            batch_size += 1;
 
-            if line_buffer_size > MAX_LINES - 256 {
+            if line_buffer_size > MAX_LINES - num_chunks {
                 // don't risk trying to add another segment.
+                if newest_first {
+                    // Segments before this one are still counted in the
+                    // frame's time, so the lines that were kept decay as usual.
+                    self.metrics.dropped += i as u64;
+                    if i > 0 {
+                        debug!("line buffer full, dropped {i} samples");
+                    }
+                } else {
+                    batch_size = n + 1;
+                }
                 break;
             }
         }
+        if newest_first {
+            // The shader expects each chunk's lines in order.
+            for lines in &mut self.chunk_lines[..num_chunks] {
+                lines.reverse();
+            }
+        }
 
         // write chunk offset/size data
         let mut offset = 0;
-        for i_chunk in 0..256 {
-            let size: u16 = self.chunk_lines[i_chunk].len().try_into().unwrap();
-            self.config.chunks[i_chunk >> 2].offset_size[i_chunk & 3] = pack2xu16([offset, size]);
+        for i_chunk in 0..num_chunks {
+            let size = self.chunk_lines[i_chunk].len();
+            self.config.chunks[i_chunk >> 2].offset_size[i_chunk & 3] =
+                pack2xu16([offset as u16, size as u16]);
             offset += size;
         }
 
@@ -404,6 +1352,8 @@ impl Scope {
 
         // remove processed samples from buffer
         if batch_size > 0 {
+            self.last_drawn
+                .extend_from_slice(&self.samples[1..=batch_size]);
             self.samples.copy_within(batch_size - 1.., 0);
             self.samples.truncate(self.samples.len() - batch_size + 1);
         }
@@ -411,6 +1361,14 @@ impl Scope {
         // finalize
         self.config.total_time = batch_size as f32;
         self.sample_count += batch_size;
+        self.config.intensity_scale = if self.normalize_intensity {
+            // Each sub-step gets its share of a frame's worth of lines.
+            let normal_lines = NORMAL_LINES_PER_FRAME / self.passes.len() as f32;
+            (normal_lines / visible_lines.max(1) as f32)
+                .clamp(1.0 / MAX_INTENSITY_SCALE, MAX_INTENSITY_SCALE)
+        } else {
+            1.0
+        };
     }
 
     pub fn draw(
@@ -419,38 +1377,567 @@ impl Scope {
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
     ) {
-        self.generate_chunks();
-        queue.write_buffer(&self.config_buffer, 0, bytemuck::bytes_of(&self.config));
-        queue.write_buffer(&self.line_buffer, 0, bytemuck::cast_slice(&self.lines));
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Scope.render_pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: frame_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations::default(),
-                })],
-                ..Default::default()
+        self.drain_source();
+        debug_assert_eq!(
+            self.config.window_size,
+            [self.persistence().width(), self.persistence().height()].map(|x| x as f32),
+            "window size doesn't match the persistence buffer",
+        );
+
+        // While held, the held samples stand in for the live ones.
+        let live_samples = self
+            .held
+            .as_ref()
+            .map(|held| std::mem::replace(&mut self.samples, held.clone()));
+        let mut remaining = match live_samples {
+            Some(_) => self.pending(),
+            None => self.sample_limit(),
+        };
+        self.last_drawn.clear();
+        self.last_drawn.extend(self.samples.first());
+        let frame_time_scale = self.frame_time_scale();
+
+        // Each sub-step draws its share of the frame's samples, in order.
+        let substeps = self.passes.len();
+        self.metrics.lines = 0;
+        self.metrics.total_time = 0.0;
+        self.metrics.busiest_chunk = 0;
+        for i in 0..substeps {
+            let sample_limit = remaining.div_ceil(substeps - i);
+            remaining -= sample_limit;
+            self.generate_chunks(sample_limit);
+            self.metrics.lines += self.lines.len();
+            self.metrics.total_time += self.config.total_time;
+            self.metrics.busiest_chunk = self.metrics.busiest_chunk.max(self.busiest_chunk());
+
+            let mut config = self.config;
+            if self.frozen {
+                config.decay = 1.0;
+                config.edge_decay = 1.0;
+                config.afterglow_decay = 1.0;
+            }
+            config.sigma *= self.beam_scale();
+            config.line_radius *= self.beam_scale();
+            config.frame_index = self.frame_count as u32;
+            if self.normalize_intensity {
+                config.intensity_scale *= frame_time_scale;
+            }
+            config.validate_and_clamp();
+            let pass = &self.passes[i];
+            queue.write_buffer(&pass.config, 0, bytemuck::bytes_of(&config));
+            queue.write_buffer(&pass.lines, 0, bytemuck::cast_slice(&self.lines));
+
+            // Only the persistence buffer matters until the last sub-step.
+            let target = (i + 1 == substeps).then_some(frame_view);
+            self.render(i, target, encoder);
+        }
+        if let Some(live_samples) = live_samples {
+            self.samples = live_samples;
+        }
+        self.frame_count += 1;
+    }
+
+    /// How long this frame stays on screen, relative to a frame at
+    /// `REFERENCE_FRAME_RATE`, so that faster displays deposit less light per
+    /// frame.
+    fn frame_time_scale(&mut self) -> f32 {
+        let now = Instant::now();
+        let interval = self
+            .frame_interval
+            .unwrap_or_else(|| match self.last_frame {
+                Some(last_frame) => now.duration_since(last_frame).as_secs_f32(),
+                None => 1.0 / REFERENCE_FRAME_RATE,
             });
+        self.last_frame = Some(now);
+        (interval * REFERENCE_FRAME_RATE).clamp(1.0 / MAX_INTENSITY_SCALE, MAX_INTENSITY_SCALE)
+    }
+
+    /// Split each frame into `substeps` persistence passes, each drawing its
+    /// share of the frame's samples in order. Each pass decays the lines of
+    /// the ones before it, so decay is ordered within the frame, and the beam
+    /// saturates between sub-steps instead of only at the end of the frame.
+    pub fn set_substeps(&mut self, substeps: usize) {
+        let substeps = substeps.max(1);
+        self.passes.truncate(substeps);
+        while self.passes.len() < substeps {
+            self.passes
+                .push(PassBuffers::new(&self.gfx, &self.uniform_bind_group_layout));
+        }
+    }
+
+    fn create_scratch(&self) -> wgpu::TextureView {
+        let texture = self.gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scope.scratch"),
+            size: wgpu::Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                ..Default::default()
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        texture.create_view(&Default::default())
+    }
+
+    /// Render throwaway frames to an offscreen target, so that pipeline
+    /// compilation and resource allocation are done before the first real frame.
+    pub fn warm_up(&mut self) {
+        let target = self.gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scope.warm_up_target"),
+            size: wgpu::Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                ..Default::default()
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&Default::default());
+
+        self.gfx
+            .queue
+            .write_buffer(&self.passes[0].config, 0, bytemuck::bytes_of(&self.config));
+        // An even number of frames leaves the ping-pong state where it started.
+        for _ in 0..2 {
+            let mut encoder = self.gfx.device.create_command_encoder(&Default::default());
+            self.render(0, Some(&target_view), &mut encoder);
+            self.gfx.queue.submit([encoder.finish()]);
+        }
+        self.gfx.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// Run one persistence pass, with the buffers of `pass`, and show the
+    /// result on `target`. Without a target, only the persistence buffer
+    /// is updated.
+    fn render(
+        &mut self,
+        pass: usize,
+        target: Option<&wgpu::TextureView>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if self.compute_persistence {
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Scope.compute_pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&self.compute_pipeline);
+                compute_pass.set_bind_group(0, &self.passes[pass].bind_group, &[]);
+                compute_pass.set_bind_group(1, &self.size_dependent.front, &[]);
+                compute_pass.dispatch_workgroups(
+                    self.size[0].div_ceil(COMPUTE_WORKGROUP_SIZE),
+                    self.size[1].div_ceil(COMPUTE_WORKGROUP_SIZE),
+                    1,
+                );
+            }
+            // The display pass reads the result through the other bind group.
+            self.size_dependent.swap();
+            if let Some(target) = target {
+                self.color_pass(
+                    &self.display_pipelines,
+                    self.blend_mode,
+                    pass,
+                    target,
+                    encoder,
+                );
+            }
+            return;
+        }
+
+        match target {
+            Some(target) => {
+                self.color_pass(&self.pipelines, self.blend_mode, pass, target, encoder)
+            }
+            None => {
+                let scratch = self.scratch.take().unwrap_or_else(|| self.create_scratch());
+                self.color_pass(&self.pipelines, BlendMode::Replace, pass, &scratch, encoder);
+                self.scratch = Some(scratch);
+            }
+        }
+        self.size_dependent.swap();
+    }
+
+    fn color_pass(
+        &self,
+        pipelines: &[wgpu::RenderPipeline; 3],
+        blend_mode: BlendMode,
+        pass: usize,
+        target: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Scope.render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: match blend_mode {
+                        BlendMode::Replace => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        // Composite over the existing contents of the target.
+                        _ => wgpu::LoadOp::Load,
+                    },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&pipelines[blend_mode as usize]);
+        render_pass.set_bind_group(0, &self.passes[pass].bind_group, &[]);
+        render_pass.set_bind_group(1, &self.size_dependent.front, &[]);
+        render_pass.set_bind_group(2, &self.background_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Draw a heatmap of the number of lines in each chunk of the last
+    /// sub-step over `target`, which should already hold the frame.
+    pub fn draw_occupancy(&self, target: &wgpu::TextureView, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Scope.occupancy_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        let last_pass = self.passes.last().unwrap();
+        render_pass.set_pipeline(&self.occupancy_pipeline);
+        render_pass.set_bind_group(0, &last_pass.bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Number of lines in the fullest chunk of the current `config`.
+    fn busiest_chunk(&self) -> usize {
+        let num_chunks = (self.config.grid_size * self.config.grid_size) as usize;
+        (0..num_chunks)
+            .map(|i_chunk| {
+                (self.config.chunks[i_chunk >> 2].offset_size[i_chunk & 3] >> 16) as usize
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Accumulate the persistence buffer in a compute pass, instead of in the
+    /// fragment shader that draws to the target. The fragment pass then only
+    /// maps the result to colors.
+    pub fn set_compute_persistence(&mut self, compute: bool) {
+        self.compute_persistence = compute;
+    }
+
+    /// The persistence buffer, as of the last draw. Each texel holds the
+    /// linear beam energy in its first channel, and the afterglow in its second.
+    pub fn persistence(&self) -> &wgpu::Texture {
+        &self.size_dependent.a
+    }
+
+    /// Change the size of the render target. This clears the persistence buffer.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        self.size = size.map(|x| x.max(1));
+        self.size_dependent =
+            SizeDependent::new(&self.gfx, &self.texture_bind_group_layout, self.size);
+        self.scratch = None;
+        self.config.window_size = self.size.map(|x| x as f32);
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.size_dependent.front, &[]);
-            render_pass.draw(0..4, 0..1);
+        let grid_size = grid_size_for(self.size);
+        if grid_size != self.config.grid_size {
+            info!("using a {grid_size}x{grid_size} chunk grid");
+            self.config.grid_size = grid_size;
         }
+    }
+}
+
+/// Size of one step between the encoded values of a color format.
+fn quantization_step(format: wgpu::TextureFormat) -> f32 {
+    match format {
+        wgpu::TextureFormat::Rgb10a2Unorm => 1.0 / 1023.0,
+        // Relative to values around 1.0, where the steps are largest.
+        wgpu::TextureFormat::Rgba16Float => 1.0 / 2048.0,
+        _ => 1.0 / 255.0,
+    }
+}
+
+/// Choose the chunk grid density for a render target size, so that each chunk
+/// covers about the same number of pixels. Coarser chunks make each pixel test
+/// more lines, while finer chunks make each line land in more chunks.
+fn grid_size_for(size: [u32; 2]) -> u32 {
+    let pixels = size[0].min(size[1]);
+    (pixels / TARGET_CHUNK_PIXELS)
+        .next_power_of_two()
+        .clamp(4, MAX_GRID_SIZE as u32)
+}
 
-        std::mem::swap(
-            &mut self.size_dependent.front,
-            &mut self.size_dependent.back,
+fn create_background_bind_group(
+    gfx: &GraphicsContext,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    image: &image::RgbaImage,
+) -> wgpu::BindGroup {
+    let size = wgpu::Extent3d {
+        width: image.width().max(1),
+        height: image.height().max(1),
+        ..Default::default()
+    };
+    let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Scope.background"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    if !image.is_empty() {
+        gfx.queue.write_texture(
+            texture.as_image_copy(),
+            image.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
         );
-        self.frame_count += 1;
     }
+    let view = texture.create_view(&Default::default());
+
+    gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scope.background_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
 
-    pub fn window_resized(&mut self) {
-        self.size_dependent = SizeDependent::new(&self.gfx, &self.texture_bind_group_layout);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::GraphicsContextInner;
+    use crate::headless::{self, Offscreen};
+    use std::sync::Arc;
 
-        let size = self.gfx.window.inner_size();
-        self.config.window_size = [size.width as f32, size.height as f32];
+    /// A device for the tests that draw, or `None` to skip them on machines
+    /// without a graphics adapter. Set `GLOWIE_SOFTWARE=1` to use a software
+    /// adapter.
+    fn test_gfx() -> Option<GraphicsContext> {
+        let instance = GraphicsContextInner::create_instance();
+        let software = std::env::var_os("GLOWIE_SOFTWARE").is_some();
+        match pollster::block_on(GraphicsContextInner::new(&instance, None, software)) {
+            Ok(gfx) => Some(Arc::new(gfx)),
+            Err(e) => {
+                eprintln!("skipping, no graphics adapter: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Draw one frame of a stationary point, `samples` long, from a fresh
+    /// scope, returning the beam's persistence.
+    fn draw_point(gfx: &GraphicsContext, samples: usize) -> Vec<f32> {
+        let size = [64, 64];
+        let target = Offscreen::new(Arc::clone(gfx), size);
+        let mut scope = Scope::new(Arc::clone(gfx), headless::FORMAT, size);
+        scope.set_decay(0.9);
+        scope.set_max_retained(samples);
+        scope.extend(std::iter::repeat_n([0.5, 0.5], samples));
+        let mut encoder = gfx.device.create_command_encoder(&Default::default());
+        scope.draw(target.view(), &mut encoder, &gfx.queue);
+        gfx.queue.submit([encoder.finish()]);
+        let texels: Vec<[f32; 2]> = bytemuck::pod_collect_to_vec(
+            &headless::read_texture(gfx, scope.persistence()).unwrap(),
+        );
+        texels.into_iter().map(|[beam, _]| beam).collect()
+    }
+
+    type Field = fn(&mut Config) -> &mut f32;
+
+    /// Every float parameter of `Config`, with its valid range.
+    const FLOAT_FIELDS: [(&str, Field, f32, f32); 18] = [
+        ("window_size.x", |c| &mut c.window_size[0], 1.0, 16384.0),
+        ("window_size.y", |c| &mut c.window_size[1], 1.0, 16384.0),
+        ("line_radius", |c| &mut c.line_radius, 0.0, 100.0),
+        ("decay", |c| &mut c.decay, 0.0, 1.0),
+        ("edge_decay", |c| &mut c.edge_decay, 0.0, 1.0),
+        ("decay_falloff", |c| &mut c.decay_falloff, 0.1, 10.0),
+        ("sigma", |c| &mut c.sigma, 1e-5, 1.0),
+        ("intensity", |c| &mut c.intensity, 0.0, 1.0),
+        (
+            "total_time",
+            |c| &mut c.total_time,
+            0.0,
+            MAX_FRAME_SAMPLES as f32,
+        ),
+        (
+            "background_opacity",
+            |c| &mut c.background_opacity,
+            0.0,
+            1.0,
+        ),
+        (
+            "intensity_scale",
+            |c| &mut c.intensity_scale,
+            0.0,
+            MAX_INTENSITY_SCALE,
+        ),
+        ("beam_color.r", |c| &mut c.beam_color[0], 0.0, 1.0),
+        ("beam_color.a", |c| &mut c.beam_color[3], 0.0, 1.0),
+        ("afterglow_color.r", |c| &mut c.afterglow_color[0], 0.0, 1.0),
+        ("afterglow_color.a", |c| &mut c.afterglow_color[3], 0.0, 1.0),
+        ("afterglow_decay", |c| &mut c.afterglow_decay, 0.0, 1.0),
+        ("afterglow_level", |c| &mut c.afterglow_level, 0.0, 1.0),
+        ("dither_step", |c| &mut c.dither_step, 0.0, 1.0),
+    ];
+
+    fn validated(field: Field, value: f32) -> f32 {
+        let mut config = Config::default();
+        *field(&mut config) = value;
+        config.validate_and_clamp();
+        *field(&mut config)
+    }
+
+    #[test]
+    fn non_finite_values_become_defaults() {
+        for (name, field, _, _) in FLOAT_FIELDS {
+            let default = *field(&mut Config::default());
+            for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                assert_eq!(validated(field, value), default, "{name} = {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        for (name, field, min, max) in FLOAT_FIELDS {
+            for (value, expected) in [
+                (min - 1.0, min),
+                (-f32::MAX, min),
+                (max + 1.0, max),
+                (f32::MAX, max),
+                (min, min),
+                (max, max),
+            ] {
+                assert_eq!(validated(field, value), expected, "{name} = {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        for (name, field, min, max) in FLOAT_FIELDS {
+            let default = *field(&mut Config::default());
+            assert!(
+                (min..=max).contains(&default),
+                "{name} defaults to {default}"
+            );
+            assert_eq!(validated(field, default), default, "{name}");
+        }
+    }
+
+    #[test]
+    fn invalid_enums_become_defaults() {
+        let default = Config::default();
+        let mut config = Config {
+            grid_size: 12,
+            beam_profile: u32::MAX,
+            blend_mode: BlendMode::Alpha as u32 + 1,
+            mirror: u32::MAX,
+            dither_srgb: 7,
+            ..default
+        };
+        config.validate_and_clamp();
+        assert_eq!(config.grid_size, default.grid_size);
+        assert_eq!(config.beam_profile, default.beam_profile);
+        assert_eq!(config.blend_mode, default.blend_mode);
+        assert_eq!(config.mirror, Mirror::Quad as u32);
+        assert_eq!(config.dither_srgb, 1);
+
+        config.grid_size = 2 * MAX_GRID_SIZE as u32;
+        config.validate_and_clamp();
+        assert_eq!(config.grid_size, default.grid_size);
+    }
+
+    /// When paced, more lines than the buffer holds must keep the newest
+    /// segments, in order within each chunk.
+    #[test]
+    fn paced_overflow_keeps_newest_segments() {
+        let Some(gfx) = test_gfx() else {
+            return;
+        };
+        let size = [64, 64];
+        let target = Offscreen::new(Arc::clone(&gfx), size);
+        let mut scope = Scope::new(Arc::clone(&gfx), headless::FORMAT, size);
+
+        // A stationary point puts every line in the same few chunks.
+        let segments = 100_000;
+        scope.set_max_retained(segments + 1);
+        scope.extend(std::iter::repeat_n([0.5, 0.5], segments + 1));
+        let mut encoder = gfx.device.create_command_encoder(&Default::default());
+        scope.draw(target.view(), &mut encoder, &gfx.queue);
+        gfx.queue.submit([encoder.finish()]);
+
+        assert!(scope.metrics.dropped > 0, "nothing was dropped");
+        assert!(scope.lines.len() <= MAX_LINES);
+        assert_eq!(scope.pending(), 0);
+        assert!(
+            scope
+                .lines
+                .iter()
+                .any(|line| line.time == (segments - 1) as f32),
+            "the newest segment was dropped"
+        );
+        let num_chunks = (scope.config.grid_size * scope.config.grid_size) as usize;
+        for i_chunk in 0..num_chunks {
+            let offset_size = scope.config.chunks[i_chunk >> 2].offset_size[i_chunk & 3];
+            let offset = (offset_size & 0xffff) as usize;
+            let size = (offset_size >> 16) as usize;
+            let lines = &scope.lines[offset..offset + size];
+            assert!(
+                lines.windows(2).all(|pair| pair[0].time < pair[1].time),
+                "chunk {i_chunk} is out of order"
+            );
+        }
+    }
+
+    /// A paced frame longer than the line buffer keeps its newest lines, whose
+    /// times are past `MAX_LINES`. They must decay as usual, not be amplified.
+    #[test]
+    fn long_frame_decays_newest_lines() {
+        let Some(gfx) = test_gfx() else {
+            return;
+        };
+        // A stationary point puts every line in the same few chunks, so only
+        // the newest few thousand fit.
+        let long = draw_point(&gfx, 100_001);
+        let short = draw_point(&gfx, 5_001);
+
+        let peak = short.iter().copied().fold(0.0, f32::max);
+        assert!(peak > 0.0 && peak < 9.0, "reference peak is {peak}");
+        for (i, (long, short)) in long.iter().zip(&short).enumerate() {
+            assert!(
+                (long - short).abs() <= 1e-3 * peak,
+                "texel {i} is {long} after a long frame, but {short} after a short one"
+            );
+        }
     }
 }
@@ -23,10 +23,10 @@ fn vs_main(
 }
 
 struct Config {
-    // y' * 16 + x' where
-    //     x' = min(u32(8.0 * (x + 1.0)), 15)
-    //     y' = min(u32(8.0 * (y + 1.0)), 15)
-    chunks: array<Chunk4, 64>,
+    // y' * grid_size + x' where
+    //     x' = min(u32(grid_size / 2 * (x + 1.0)), grid_size - 1)
+    //     y' = min(u32(grid_size / 2 * (y + 1.0)), grid_size - 1)
+    chunks: array<Chunk4, 256>,
 
     window_size: vec2<f32>,
     line_radius: f32,
@@ -34,6 +34,21 @@ struct Config {
     sigma: f32,
     intensity: f32,
     total_time: f32,
+    beam_profile: u32,
+    background_opacity: f32,
+    blend_mode: u32,
+    intensity_scale: f32,
+    edge_decay: f32,
+    decay_falloff: f32,
+    grid_size: u32,
+    mirror: u32,
+    afterglow_decay: f32,
+    beam_color: vec4<f32>,
+    afterglow_color: vec4<f32>,
+    afterglow_level: f32,
+    dither_step: f32,
+    frame_index: u32,
+    dither_srgb: u32,
 };
 
 struct Chunk4 {
@@ -44,7 +59,7 @@ struct Chunk4 {
 struct Line {
     // 2x16snorm
     start: u32,
-    // 2x16snorm
+    // 2x16snorm, halved so that segments across the whole screen fit
     v: u32,
     time: f32,
 }
@@ -56,47 +71,101 @@ var<uniform> config: Config;
 var<storage> lines: array<Line>;
 
 @group(1) @binding(0)
-var tex_in: texture_storage_2d<r32float, read>;
+var tex_in: texture_2d<f32>;
 
 @group(1) @binding(1)
-var tex_out: texture_storage_2d<r32float, write>;
+var tex_out: texture_storage_2d<rg32float, write>;
+
+@group(2) @binding(0)
+var background: texture_2d<f32>;
+
+@group(2) @binding(1)
+var background_sampler: sampler;
 
 const e = 2.7182818459045;
 const pi = 3.141592653589793;
 const inv_sqrt_2pi = 0.3989422804014327;
 
+const BEAM_GAUSSIAN = 0u;
+const BEAM_EXPONENTIAL = 1u;
+const BEAM_HARD = 2u;
+
+const BLEND_ALPHA = 2u;
+
+const MIRROR_X = 1u;
+const MIRROR_Y = 2u;
+
+// Each profile integrates to `intensity` across the beam.
 fn excitation(distance: f32) -> f32 {
+    if config.beam_profile == BEAM_EXPONENTIAL {
+        return config.intensity * 0.5 / config.sigma
+            * exp(-distance / config.sigma);
+    }
+    if config.beam_profile == BEAM_HARD {
+        // Antialiased over one pixel, so edges don't depend on the angle.
+        let pixel = 2.0 / min(config.window_size.x, config.window_size.y);
+        let coverage = clamp((config.sigma - distance) / pixel + 0.5, 0.0, 1.0);
+        return config.intensity * 0.5 / config.sigma * coverage;
+    }
     return config.intensity * inv_sqrt_2pi / config.sigma
         * pow(e, -0.5 * pow(distance / config.sigma, 2.0));
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // XXX: this is not the same as the value from the vertex shader;
-    // it is actually pixel coordinates.
-    let frag_coord = vec2<u32>(in.clip_position.xy);
-
-    var pos = in.pos;
+// Position in the scope's square, from normalized device coordinates.
+fn scope_pos(ndc: vec2<f32>) -> vec2<f32> {
+    var pos = ndc;
     let aspect = config.window_size.x / config.window_size.y;
     if (aspect > 1.0) {
         pos.x *= aspect;
     } else {
         pos.y /= aspect;
     }
+    return pos;
+}
 
-    if (max(abs(pos.x), abs(pos.y)) > 1.1) {
-        // Outside of the center square
-        discard;
+fn is_visible(pos: vec2<f32>) -> bool {
+    // Outside of the center square, with a small margin.
+    return max(abs(pos.x), abs(pos.y)) <= 1.1;
+}
+
+// Mirroring folds the screen onto one half or quadrant of the signal.
+fn signal_pos(screen_pos: vec2<f32>) -> vec2<f32> {
+    var pos = screen_pos;
+    if (config.mirror & MIRROR_X) != 0u {
+        pos.x = abs(pos.x);
+    }
+    if (config.mirror & MIRROR_Y) != 0u {
+        pos.y = abs(pos.y);
     }
+    return pos;
+}
+
+// Offset and size of the chunk containing a position, packed as in `Chunk4`.
+fn chunk_at(pos: vec2<f32>) -> u32 {
+    let grid_size = i32(config.grid_size);
+    let half_grid = 0.5 * f32(grid_size);
+    let chunk_x = clamp(i32(half_grid * (pos.x + 1.0)), 0, grid_size - 1);
+    let chunk_y = clamp(i32(half_grid * (pos.y + 1.0)), 0, grid_size - 1);
+    let i_chunk = chunk_y * grid_size + chunk_x;
+    return config.chunks[i_chunk >> 2].offset_size[i_chunk & 3];
+}
 
-    let chunk_x = clamp(i32(8.0 * (pos.x + 1.0)), 0, 15);
-    let chunk_y = clamp(i32(8.0 * (pos.y + 1.0)), 0, 15);
-    let i_chunk = chunk_y * 16 + chunk_x;
-    let chunk_offset_size = config.chunks[i_chunk >> 2].offset_size[i_chunk & 3];
+// The persistence buffer at a texel, after this frame's decay and lines.
+fn accumulate(screen_pos: vec2<f32>, texel: vec2<u32>) -> vec2<f32> {
+    let pos = signal_pos(screen_pos);
+    let chunk_offset_size = chunk_at(pos);
     let chunk_offset = chunk_offset_size & 0xffff;
     let chunk_size = chunk_offset_size >> 16;
 
-    let prev = textureLoad(tex_in, frag_coord).x;
+    // Decay blends from the center to the corners of the visible square.
+    let radius = min(length(pos) / sqrt(2.0), 1.0);
+    let decay = mix(config.decay, config.edge_decay, pow(radius, config.decay_falloff));
+
+    // The beam and its afterglow, each decaying at its own rate.
+    let decays = vec2<f32>(decay, config.afterglow_decay);
+    let levels = vec2<f32>(1.0, config.afterglow_level);
+
+    let prev = textureLoad(tex_in, texel, 0).xy;
     var next = prev;
     var t = 0.0;
 
@@ -105,12 +174,12 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
         // Calculate decay for time before this line.
         let delta_t = line.time - t;
-        next *= pow(config.decay, delta_t);
+        next *= pow(decays, vec2(delta_t));
         t = line.time;
 
         // Contribution from line
         let start = unpack2x16snorm(line.start);
-        let v = unpack2x16snorm(line.v);
+        let v = 2.0 * unpack2x16snorm(line.v);
         let u = pos - start;
         var disp = u;
         if dot(v, v) != 0.0 {
@@ -119,18 +188,132 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
             disp -= proj;
         }
 
-        let x = excitation(length(disp)) / (3 * config.sigma + length(v));
+        let x = config.intensity_scale * excitation(length(disp))
+            / (3 * config.sigma + length(v));
         if x == x {
             // Only finite numbers please
-            next += x;
+            next += levels * x;
         }
 
     }
-    next *= pow(config.decay, config.total_time - t);
+    // The frame never ends before its last line.
+    next *= pow(decays, vec2(max(config.total_time - t, 0.0)));
 
     // Clipping
-    next = clamp(next, 0.0, 10.0);
+    return clamp(next, vec2(0.0), vec2(10.0));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let c1 = max(c, vec3(0.0));
+    return select(1.055 * pow(c1, vec3(1.0 / 2.4)) - 0.055, 12.92 * c1, c1 <= vec3(0.0031308));
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let c1 = max(c, vec3(0.0));
+    return select(pow((c1 + 0.055) / 1.055, vec3(2.4)), c1 / 12.92, c1 <= vec3(0.04045));
+}
+
+// Noise of up to half of `dither_step` either way, to break up banding.
+fn dither(color: vec3<f32>, pixel: vec2<u32>) -> vec3<f32> {
+    if config.dither_step == 0.0 {
+        return color;
+    }
+    // Interleaved gradient noise, shifted every frame.
+    let p = vec2<f32>(pixel) + 5.588238 * f32(config.frame_index % 64u);
+    let noise = (fract(52.9829189 * fract(dot(p, vec2(0.06711056, 0.00583715)))) - 0.5)
+        * config.dither_step;
+    if config.dither_srgb == 0u {
+        return color + noise;
+    }
+    // The target quantizes after encoding, so the steps are even in sRGB.
+    return srgb_to_linear(linear_to_srgb(color) + noise);
+}
 
-    textureStore(tex_out, frag_coord, vec4(next));
-    return vec4<f32>((next - 1.0) / 2.5, next, (next - 1.0) / 2.0, 1.0);
+// The displayed color for a persistence value.
+fn shade(next: vec2<f32>, screen_pos: vec2<f32>, pixel: vec2<u32>) -> vec4<f32> {
+    // The missing components ramp in above full intensity, whitening the core.
+    let color = config.beam_color.rgb;
+    let whitening = (1.0 - color) * vec3<f32>(0.4, 0.5, 0.5) * (next.x - 1.0);
+    let flash = max(color * next.x + whitening, vec3(0.0));
+    let beam = flash + config.afterglow_color.rgb * next.y;
+
+    // Background is composited additively, underneath the beam.
+    let uv = vec2<f32>(0.5 + 0.5 * screen_pos.x, 0.5 - 0.5 * screen_pos.y);
+    let bg = textureSampleLevel(background, background_sampler, uv, 0.0).rgb;
+    let out = dither(beam + config.background_opacity * bg, pixel);
+    if config.blend_mode == BLEND_ALPHA {
+        // Fade out where the image is dark, revealing the target underneath.
+        let alpha = clamp(max(out.r, max(out.g, out.b)), 0.0, 1.0);
+        return vec4<f32>(out / max(alpha, 1e-6), alpha);
+    }
+    return vec4<f32>(out, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // XXX: this is not the same as the value from the vertex shader;
+    // it is actually pixel coordinates.
+    let frag_coord = vec2<u32>(in.clip_position.xy);
+
+    let pos = scope_pos(in.pos);
+    if !is_visible(pos) {
+        discard;
+    }
+
+    let next = accumulate(pos, frag_coord);
+    textureStore(tex_out, frag_coord, vec4(next, 0.0, 0.0));
+    return shade(next, pos, frag_coord);
+}
+
+// Accumulates the whole persistence buffer, for `fs_display` to show.
+@compute @workgroup_size(8, 8)
+fn cs_accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<u32>(config.window_size);
+    if any(id.xy >= size) {
+        return;
+    }
+    let ndc = vec2<f32>(
+        2.0 * (f32(id.x) + 0.5) / config.window_size.x - 1.0,
+        1.0 - 2.0 * (f32(id.y) + 0.5) / config.window_size.y,
+    );
+    let pos = scope_pos(ndc);
+    if !is_visible(pos) {
+        return;
+    }
+    textureStore(tex_out, id.xy, vec4(accumulate(pos, id.xy), 0.0, 0.0));
+}
+
+// Shows the persistence buffer written by `cs_accumulate`.
+@fragment
+fn fs_display(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frag_coord = vec2<u32>(in.clip_position.xy);
+    let pos = scope_pos(in.pos);
+    if !is_visible(pos) {
+        discard;
+    }
+    return shade(textureLoad(tex_in, frag_coord, 0).xy, pos, frag_coord);
+}
+
+// Heatmap of the number of lines in each chunk, drawn over the scope.
+@fragment
+fn fs_occupancy(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pos = signal_pos(scope_pos(in.pos));
+    if !is_visible(pos) {
+        discard;
+    }
+    // On a log scale, up to the most a chunk can hold.
+    let size = f32(chunk_at(pos) >> 16);
+    let heat = log2(size + 1.0) / 16.0;
+
+    // Outline each chunk, so that empty ones can be told apart.
+    let cell = fract(0.5 * f32(config.grid_size) * (pos + 1.0));
+    let pixel = f32(config.grid_size) / min(config.window_size.x, config.window_size.y);
+    let edge = any(min(cell, 1.0 - cell) < vec2(pixel));
+
+    if size == 0.0 && !edge {
+        discard;
+    }
+    let color = mix(vec3(0.0, 0.2, 1.0), vec3(1.0, 0.1, 0.0), heat);
+    let alpha = select(0.2 + 0.5 * heat, 0.5, edge);
+    return vec4<f32>(color, alpha);
 }
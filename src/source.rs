@@ -0,0 +1,101 @@
+//! Sources of stereo frames for the scope and the audio output.
+
+use anyhow::{bail, ensure};
+use std::path::{Path, PathBuf};
+
+/// A stream of stereo frames at a fixed sample rate.
+pub trait SampleSource: Send {
+    /// Read the next frame, or `None` at the end of the stream.
+    fn next_frame(&mut self) -> Option<[f32; 2]>;
+
+    /// Frames per second.
+    fn sample_rate(&self) -> u32;
+
+    /// Move to the frame at index `frame`, or to the end if the stream is
+    /// shorter, returning the new position.
+    fn seek(&mut self, _frame: u64) -> anyhow::Result<u64> {
+        bail!("this source can't seek")
+    }
+}
+
+impl<S: SampleSource + ?Sized> SampleSource for Box<S> {
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        (**self).next_frame()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn seek(&mut self, frame: u64) -> anyhow::Result<u64> {
+        (**self).seek(frame)
+    }
+}
+
+/// A stereo audio file, decoded by `audrey`.
+pub struct FileSource {
+    path: PathBuf,
+    reader: audrey::read::BufFileReader,
+    sample_rate: u32,
+    /// Index of the next frame.
+    position: u64,
+}
+
+impl FileSource {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let reader = audrey::open(path)?;
+        let descr = reader.description();
+        ensure!(
+            descr.channel_count() == 2,
+            "audio channels must be equal to 2 (stereo)"
+        );
+        Ok(Self {
+            path: path.to_owned(),
+            sample_rate: descr.sample_rate(),
+            reader,
+            position: 0,
+        })
+    }
+}
+
+impl FileSource {
+    /// Skip ahead by up to `frames`, returning the number of frames skipped.
+    pub fn skip(&mut self, frames: u64) -> u64 {
+        let mut skipped = 0;
+        while skipped < frames && self.next_frame().is_some() {
+            skipped += 1;
+        }
+        skipped
+    }
+}
+
+impl SampleSource for FileSource {
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        let frame = self
+            .reader
+            .frames::<[f32; 2]>()
+            .next()
+            .map(|result| result.expect("read error"))?;
+        self.position += 1;
+        Some(frame)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn seek(&mut self, frame: u64) -> anyhow::Result<u64> {
+        // The decoder only reads forward, so going back starts over.
+        if frame < self.position {
+            *self = Self::open(&self.path)?;
+        }
+        self.skip(frame - self.position);
+        Ok(self.position)
+    }
+}
+
+/// Iterate over the frames of a source.
+pub fn frames(source: &mut impl SampleSource) -> impl Iterator<Item = [f32; 2]> + '_ {
+    std::iter::from_fn(|| source.next_frame())
+}
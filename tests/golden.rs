@@ -0,0 +1,288 @@
+//! Golden-image tests for the renderer.
+//!
+//! Known signals are rendered with `glowie capture` and compared against the
+//! images in `tests/golden/`. To regenerate the reference images after an
+//! intentional change to the rendering, run with `GLOWIE_BLESS=1`. To render
+//! on a software adapter, for machines without a GPU, set `GLOWIE_SOFTWARE=1`.
+
+use image::RgbaImage;
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SAMPLE_RATE: u32 = 48000;
+const SIZE: u32 = 64;
+const DURATION: f32 = 0.1;
+
+/// Maximum per-channel difference for a pixel to be considered equal.
+const PIXEL_TOLERANCE: u8 = 8;
+/// Maximum fraction of pixels that may differ.
+const MISMATCH_TOLERANCE: f32 = 0.005;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn write_wav(path: &Path, signal: impl Fn(f32) -> [f32; 2]) {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    let num_frames = (DURATION * SAMPLE_RATE as f32) as usize;
+    for i in 0..num_frames {
+        let [x, y] = signal(i as f32 / SAMPLE_RATE as f32);
+        writer.write_sample(x).unwrap();
+        writer.write_sample(y).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+/// Render a signal with `glowie capture`, returning the path of the image.
+fn capture(name: &str, size: u32, signal: impl Fn(f32) -> [f32; 2], args: &[&str]) -> PathBuf {
+    let temp_dir = std::env::temp_dir().join(format!("glowie-golden-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let wav_path = temp_dir.join(format!("{name}.wav"));
+    let out_path = temp_dir.join(format!("{name}.png"));
+    write_wav(&wav_path, signal);
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_glowie"));
+    command.arg("capture");
+    if std::env::var_os("GLOWIE_SOFTWARE").is_some() {
+        command.arg("--software");
+    }
+    if !args.contains(&"--decay") {
+        command.arg("--decay").arg("0.9999");
+    }
+    let status = command
+        .arg(&wav_path)
+        .arg("--at")
+        .arg(DURATION.to_string())
+        .arg("--out")
+        .arg(&out_path)
+        .arg("--width")
+        .arg(size.to_string())
+        .arg("--height")
+        .arg(size.to_string())
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "capture failed for {name}");
+    out_path
+}
+
+/// Number of pixels that differ by more than `PIXEL_TOLERANCE` in any channel.
+fn mismatched_pixels(a: &RgbaImage, b: &RgbaImage) -> usize {
+    assert_eq!(a.dimensions(), b.dimensions());
+    a.pixels()
+        .zip(b.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0)
+                .any(|(&a, b)| a.abs_diff(b) > PIXEL_TOLERANCE)
+        })
+        .count()
+}
+
+fn check(name: &str, signal: impl Fn(f32) -> [f32; 2]) {
+    let out_path = capture(name, SIZE, signal, &[]);
+
+    let golden_path = golden_dir().join(format!("{name}.png"));
+    if std::env::var_os("GLOWIE_BLESS").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        std::fs::copy(&out_path, &golden_path).unwrap();
+        return;
+    }
+
+    let actual = image::open(&out_path).unwrap().into_rgba8();
+    let expected = image::open(&golden_path)
+        .unwrap_or_else(|e| {
+            panic!(
+                "missing golden image {} ({e}); run with GLOWIE_BLESS=1 to create it",
+                golden_path.display()
+            )
+        })
+        .into_rgba8();
+    let mismatched = mismatched_pixels(&actual, &expected);
+    let fraction = mismatched as f32 / (SIZE * SIZE) as f32;
+    assert!(
+        fraction <= MISMATCH_TOLERANCE,
+        "{name}: {mismatched} pixels differ from {}",
+        golden_path.display()
+    );
+}
+
+#[test]
+fn circle() {
+    check("circle", |t| {
+        let phase = 2.0 * PI * 100.0 * t;
+        [0.8 * phase.cos(), 0.8 * phase.sin()]
+    });
+}
+
+#[test]
+fn diagonal() {
+    check("diagonal", |t| {
+        let x = 0.8 * (2.0 * PI * 100.0 * t).sin();
+        [x, x]
+    });
+}
+
+#[test]
+fn cross() {
+    check("cross", |t| {
+        let x = 0.8 * (2.0 * PI * 100.0 * t).sin();
+        if t < DURATION / 2.0 {
+            [x, 0.0]
+        } else {
+            [0.0, x]
+        }
+    });
+}
+
+/// Triangle wave between -1 and 1.
+fn triangle(t: f32) -> f32 {
+    let phase = (100.0 * t).fract();
+    4.0 * (phase - 0.5).abs() - 1.0
+}
+
+/// The beam must look equally thick at any angle.
+#[test]
+fn width_is_isotropic() {
+    const WIDE_SIZE: u32 = 128;
+    // Both lines are 1.6 units long, traced at the same speed.
+    const HALF_LENGTH: f32 = 0.8;
+    let lit_pixels = |name, signal: fn(f32) -> [f32; 2]| {
+        let path = capture(name, WIDE_SIZE, signal, &["--beam-width", "0.02"]);
+        let image = image::open(path).unwrap().into_rgba8();
+        image.pixels().filter(|p| p.0[1] > 128).count() as f32
+    };
+    let horizontal = lit_pixels("width-horizontal", |t| [HALF_LENGTH * triangle(t), 0.0]);
+    let diagonal = lit_pixels("width-diagonal", |t| {
+        let x = HALF_LENGTH * std::f32::consts::FRAC_1_SQRT_2 * triangle(t);
+        [x, x]
+    });
+    assert!(horizontal > 0.0, "horizontal line not drawn");
+    let ratio = diagonal / horizontal;
+    assert!(
+        (0.9..=1.1).contains(&ratio),
+        "diagonal line covers {diagonal} pixels, horizontal covers {horizontal}"
+    );
+}
+
+/// With normalized intensity, the refresh rate must not change the brightness.
+#[test]
+fn brightness_is_refresh_rate_independent() {
+    let energy = |name, frame_rate: &str| {
+        let path = capture(
+            name,
+            SIZE,
+            |t| {
+                let phase = 2.0 * PI * 100.0 * t;
+                [0.8 * phase.cos(), 0.8 * phase.sin()]
+            },
+            &["--normalize-intensity", "--frame-rate", frame_rate],
+        );
+        let image = image::open(path).unwrap().into_rgba8();
+        image.pixels().map(|p| p.0[1] as f32).sum::<f32>()
+    };
+    let slow = energy("refresh-60", "60");
+    let fast = energy("refresh-144", "144");
+    assert!(slow > 0.0, "nothing drawn at 60 Hz");
+    let ratio = fast / slow;
+    assert!(
+        (0.9..=1.1).contains(&ratio),
+        "total brightness is {slow} at 60 Hz, but {fast} at 144 Hz"
+    );
+}
+
+/// With normalized intensity, drawing the same path with more lines per frame
+/// must not change the total brightness.
+#[test]
+fn brightness_is_line_count_independent() {
+    let energy = |name: &str, visual_rate: &str| {
+        let exr_path = std::env::temp_dir()
+            .join(format!("glowie-golden-{}", std::process::id()))
+            .join(format!("{name}.exr"));
+        capture(
+            name,
+            SIZE,
+            |t| {
+                let phase = 2.0 * PI * 100.0 * t;
+                [0.8 * phase.cos(), 0.8 * phase.sin()]
+            },
+            &[
+                "--normalize-intensity",
+                "--frame-rate",
+                "60",
+                "--decay",
+                "1",
+                "--visual-rate",
+                visual_rate,
+                "--exr",
+                exr_path.to_str().unwrap(),
+            ],
+        );
+        // Compare the linear persistence, since the PNG is tone mapped.
+        let image = exr::prelude::read_first_flat_layer_from_file(&exr_path).unwrap();
+        let beam = image
+            .layer_data
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.to_string() == "Y")
+            .unwrap();
+        beam.sample_data.values_as_f32().sum::<f32>()
+    };
+    let sparse = energy("lines-48k", "48000");
+    let dense = energy("lines-96k", "96000");
+    assert!(sparse > 0.0, "nothing drawn at 48 kHz");
+    let ratio = dense / sparse;
+    assert!(
+        (0.9..=1.1).contains(&ratio),
+        "total brightness is {sparse} at 48 kHz, but {dense} at 96 kHz"
+    );
+}
+
+/// The compute path must accumulate the same image as the fragment path.
+#[test]
+fn compute_persistence_matches() {
+    let circle = |t: f32| {
+        let phase = 2.0 * PI * 100.0 * t;
+        [0.8 * phase.cos(), 0.8 * phase.sin()]
+    };
+    let fragment = capture("persistence-fragment", SIZE, circle, &[]);
+    let compute = capture(
+        "persistence-compute",
+        SIZE,
+        circle,
+        &["--compute-persistence"],
+    );
+
+    let fragment = image::open(fragment).unwrap().into_rgba8();
+    let compute = image::open(compute).unwrap().into_rgba8();
+    let mismatched = mismatched_pixels(&fragment, &compute);
+    assert!(
+        mismatched as f32 / (SIZE * SIZE) as f32 <= MISMATCH_TOLERANCE,
+        "{mismatched} pixels differ between the fragment and compute paths"
+    );
+}
+
+/// More lines than a chunk can hold must not panic, and must still show the signal.
+#[test]
+fn overflowing_chunk_is_drawn() {
+    // A stationary point puts every line in the same few chunks.
+    let path = capture(
+        "overflow",
+        SIZE,
+        |_| [0.5, 0.5],
+        &["--visual-rate", "1000000"],
+    );
+    let image = image::open(path).unwrap().into_rgba8();
+    let spot = image.get_pixel(3 * SIZE / 4, SIZE / 4).0[1];
+    let corner = image.get_pixel(0, SIZE - 1).0[1];
+    assert!(spot > 128, "point not drawn, brightness {spot}");
+    assert!(corner < 16, "stray lines drawn, corner brightness {corner}");
+}